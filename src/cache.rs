@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path,PathBuf};
+use std::sync::{Arc,Mutex};
+use futures::future;
+use futures::Future;
+use hex;
+
+use crate::store::{Store,StoreFuture};
+
+/// A persistent cache of blocks in local files, sitting in front of another [store].
+///
+/// Blocks are read from the cache before they are fetched from the store behind it,
+/// and every block added or fetched through the cache is kept in it. The latest heads of logs
+/// are recorded in the cache as well, so that logs can be restored from it after a restart.
+///
+/// Once the blocks take more than the given number of bytes,
+/// the least recently used blocks are evicted from the cache.
+/// When the cache is reopened, blocks are ranked by the time they were cached.
+///
+/// ```ignore
+/// let store = Rc::new(FileCache::open(IpfsClient::default(),"/var/cache/log",64 << 20).unwrap());
+/// let log = Log::from_cached_heads(store,identity,LogOptions::new(),"some_id");
+/// ```
+///
+/// [store]: ../store/trait.Store.html
+pub struct FileCache<S> {
+	store: S,
+	blocks: Arc<Blocks>,
+}
+
+//the cached blocks, shared with the futures of the cache
+struct Blocks {
+	dir: PathBuf,
+	limit: u64,
+	index: Mutex<Index>,
+}
+
+//the cached blocks, ranked by a counter of accesses so that the least recently used comes first
+struct Index {
+	order: BTreeMap<u64,String>,
+	ranks: HashMap<String,u64>,
+	sizes: HashMap<String,u64>,
+	counter: u64,
+	total: u64,
+}
+
+impl Index {
+	//ranks the block with the hash `hash` as the most recently used
+	fn touch (&mut self, hash: &str) {
+		if let Some(rank) = self.ranks.insert(hash.to_owned(),self.counter) {
+			self.order.remove(&rank);
+		}
+		self.order.insert(self.counter,hash.to_owned());
+		self.counter += 1;
+	}
+}
+
+impl<S: Store> FileCache<S> {
+	/// Opens the cache in the directory `dir` in front of the store `store`,
+	/// keeping at most `limit` bytes of blocks in it.
+	///
+	/// Creates the directory if it does not exist.
+	pub fn open<P: AsRef<Path>> (store: S, dir: P, limit: u64) -> io::Result<FileCache<S>> {
+		let dir = dir.as_ref().to_owned();
+		fs::create_dir_all(dir.join("blocks"))?;
+		fs::create_dir_all(dir.join("heads"))?;
+		let mut blocks = Vec::new();
+		for f in fs::read_dir(dir.join("blocks"))? {
+			let f = f?;
+			let m = f.metadata()?;
+			blocks.push((m.modified()?,f.file_name().to_string_lossy().into_owned(),m.len()));
+		}
+		blocks.sort();
+		let mut index = Index {
+			order: BTreeMap::new(),
+			ranks: HashMap::new(),
+			sizes: blocks.iter().map(|x| (x.1.to_owned(),x.2)).collect(),
+			counter: 0,
+			total: blocks.iter().map(|x| x.2).sum(),
+		};
+		for b in &blocks {
+			index.touch(&b.1);
+		}
+		let blocks = Blocks {
+			dir: dir,
+			limit: limit,
+			index: Mutex::new(index),
+		};
+		blocks.evict();
+		Ok(FileCache {
+			store: store,
+			blocks: Arc::new(blocks),
+		})
+	}
+
+	/// Returns the total size of the cached blocks in bytes.
+	pub fn size (&self) -> u64 {
+		self.blocks.index.lock().unwrap().total
+	}
+
+	/// Returns `true` if the block with the hash `hash` is in the cache, otherwise returns `false`.
+	pub fn contains (&self, hash: &str) -> bool {
+		self.blocks.index.lock().unwrap().sizes.contains_key(hash)
+	}
+
+	fn heads_path (&self, log_id: &str) -> PathBuf {
+		self.blocks.dir.join("heads").join(hex::encode(log_id))
+	}
+}
+
+impl Blocks {
+	fn block_path (&self, hash: &str) -> Option<PathBuf> {
+		//hashes are used as file names as such
+		if hash.is_empty() || !hash.chars().all(|c| c.is_ascii_alphanumeric()) {
+			return None;
+		}
+		Some(self.dir.join("blocks").join(hash))
+	}
+
+	fn read (&self, hash: &str) -> Option<Vec<u8>> {
+		if !self.index.lock().unwrap().sizes.contains_key(hash) {
+			return None;
+		}
+		let data = fs::read(self.block_path(hash)?).ok()?;
+		self.index.lock().unwrap().touch(hash);
+		Some(data)
+	}
+
+	fn insert (&self, hash: &str, data: &[u8]) -> io::Result<()> {
+		let path = match self.block_path(hash) {
+			Some(p)	=>	p,
+			None	=>	return Ok(()),
+		};
+		fs::write(path,data)?;
+		{
+			let mut index = self.index.lock().unwrap();
+			if let Some(size) = index.sizes.insert(hash.to_owned(),data.len() as u64) {
+				index.total -= size;
+			}
+			index.total += data.len() as u64;
+			index.touch(hash);
+		}
+		self.evict();
+		Ok(())
+	}
+
+	fn evict (&self) {
+		let mut index = self.index.lock().unwrap();
+		while index.total > self.limit {
+			let (rank,h) = match index.order.iter().next() {
+				Some((&rank,h))	=>	(rank,h.to_owned()),
+				None			=>	break,
+			};
+			index.order.remove(&rank);
+			index.ranks.remove(&h);
+			let size = index.sizes.remove(&h).unwrap_or(0);
+			index.total -= size;
+			//a block left behind is only read if indexed again, after being overwritten
+			let _ = fs::remove_file(self.dir.join("blocks").join(&h));
+		}
+	}
+}
+
+impl<S: Store> Store for FileCache<S> {
+	fn add (&self, data: Vec<u8>) -> StoreFuture<String> {
+		let blocks = self.blocks.clone();
+		Box::new(self.store.add(data.clone()).map(move |hash| {
+			//the cache is best effort, a failed write only costs a refetch
+			let _ = blocks.insert(&hash,&data);
+			hash
+		}))
+	}
+
+	fn cat (&self, hash: &str) -> StoreFuture<Vec<u8>> {
+		if let Some(data) = self.blocks.read(hash) {
+			return Box::new(future::ok(data));
+		}
+		let blocks = self.blocks.clone();
+		let hash = hash.to_owned();
+		Box::new(self.store.cat(&hash).map(move |data| {
+			let _ = blocks.insert(&hash,&data);
+			data
+		}))
+	}
+
+	fn heads (&self, log_id: &str) -> Option<Vec<String>> {
+		fs::read(self.heads_path(log_id)).ok().and_then(|x| serde_json::from_slice(&x).ok()).
+		or_else(|| self.store.heads(log_id))
+	}
+
+	fn set_heads (&self, log_id: &str, heads: &[String]) {
+		let _ = fs::write(self.heads_path(log_id),serde_json::to_vec(heads).unwrap());
+		self.store.set_heads(log_id,heads);
+	}
+
+	fn pin (&self, hash: &str) {
+		self.store.pin(hash);
+	}
+
+	fn unpin (&self, hash: &str) {
+		self.store.unpin(hash);
+	}
+}
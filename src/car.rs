@@ -0,0 +1,223 @@
+use std::io;
+use std::io::{Read,Write};
+use sha2::{Sha256,Digest};
+
+//multicodec codes
+const RAW: u64 = 0x55;
+const SHA2_256: u64 = 0x12;
+
+/// A block of an archive, as its binary CID and data.
+pub type Block = (Vec<u8>,Vec<u8>);
+
+/// Returns the binary [CIDv1] of `data` as a raw block hashed with SHA-256.
+///
+/// [CIDv1]: https://github.com/multiformats/cid
+pub fn cid (data: &[u8]) -> Vec<u8> {
+	let mut cid = Vec::new();
+	write_varint(&mut cid,1);
+	write_varint(&mut cid,RAW);
+	write_varint(&mut cid,SHA2_256);
+	write_varint(&mut cid,32);
+	cid.extend_from_slice(&Sha256::digest(data));
+	cid
+}
+
+/// Writes a [CARv1] archive with the root CIDs `roots` and the blocks `blocks`.
+///
+/// Only the framing is CARv1: the blocks are whatever the caller gives,
+/// e.g. [`Log::export_car`] writes the data of the store as raw blocks.
+///
+/// [`Log::export_car`]: ../log/struct.Log.html#method.export_car
+/// [CARv1]: https://ipld.io/specs/transport/car/carv1/
+pub fn write_car<W: Write> (mut w: W, roots: &[Vec<u8>], blocks: &[Block]) -> io::Result<()> {
+	//the header is the DAG-CBOR map {"roots": [CID...], "version": 1}
+	let mut header = Vec::new();
+	write_cbor_head(&mut header,5,2);
+	write_cbor_text(&mut header,"roots");
+	write_cbor_head(&mut header,4,roots.len() as u64);
+	for r in roots {
+		write_cbor_head(&mut header,6,42);
+		write_cbor_head(&mut header,2,r.len() as u64 + 1);
+		header.push(0);
+		header.extend_from_slice(r);
+	}
+	write_cbor_text(&mut header,"version");
+	write_cbor_head(&mut header,0,1);
+
+	let mut buf = Vec::new();
+	write_varint(&mut buf,header.len() as u64);
+	w.write_all(&buf)?;
+	w.write_all(&header)?;
+	for (cid,data) in blocks {
+		let mut buf = Vec::new();
+		write_varint(&mut buf,(cid.len() + data.len()) as u64);
+		w.write_all(&buf)?;
+		w.write_all(cid)?;
+		w.write_all(data)?;
+	}
+	w.flush()
+}
+
+/// Reads a [CARv1] archive, verifying the SHA-256 digests of its blocks.
+///
+/// Returns the root CIDs and the blocks.
+/// Fails with [`InvalidData`] if the archive is malformed, a block is not hashed with SHA-256
+/// or its data does not match its CID.
+///
+/// [CARv1]: https://ipld.io/specs/transport/car/carv1/
+/// [`InvalidData`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.InvalidData
+pub fn read_car<R: Read> (mut r: R) -> io::Result<(Vec<Vec<u8>>,Vec<Block>)> {
+	let mut bytes = Vec::new();
+	r.read_to_end(&mut bytes)?;
+	let mut pos = 0;
+	let len = read_varint(&bytes,&mut pos)? as usize;
+	let header = take(&bytes,&mut pos,len)?;
+	let roots = read_header(header)?;
+
+	let mut blocks = Vec::new();
+	while pos < bytes.len() {
+		let len = read_varint(&bytes,&mut pos)? as usize;
+		let section = take(&bytes,&mut pos,len)?;
+		let mut p = 0;
+		let digest = read_cid(section,&mut p)?;
+		let (cid,data) = section.split_at(p);
+		if Sha256::digest(data)[..] != digest[..] {
+			return Err(invalid("block does not match its CID"));
+		}
+		blocks.push((cid.to_vec(),data.to_vec()));
+	}
+	Ok((roots,blocks))
+}
+
+fn invalid (msg: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData,msg)
+}
+
+fn take<'a> (bytes: &'a [u8], pos: &mut usize, len: usize) -> io::Result<&'a [u8]> {
+	if bytes.len() - *pos < len {
+		return Err(invalid("unexpected end of archive"));
+	}
+	*pos += len;
+	Ok(&bytes[*pos - len..*pos])
+}
+
+fn write_varint (buf: &mut Vec<u8>, mut n: u64) {
+	while n >= 0x80 {
+		buf.push((n as u8 & 0x7f) | 0x80);
+		n >>= 7;
+	}
+	buf.push(n as u8);
+}
+
+fn read_varint (bytes: &[u8], pos: &mut usize) -> io::Result<u64> {
+	let mut n = 0u64;
+	for shift in (0..64).step_by(7) {
+		let b = take(bytes,pos,1)?[0];
+		n |= ((b & 0x7f) as u64) << shift;
+		if b & 0x80 == 0 {
+			return Ok(n);
+		}
+	}
+	Err(invalid("varint too long"))
+}
+
+//returns the SHA-256 digest of the CID
+fn read_cid<'a> (bytes: &'a [u8], pos: &mut usize) -> io::Result<&'a [u8]> {
+	//a CIDv0 is a bare SHA-256 multihash
+	if bytes[*pos..].starts_with(&[0x12,0x20]) {
+		*pos += 2;
+		return take(bytes,pos,32);
+	}
+	if read_varint(bytes,pos)? != 1 {
+		return Err(invalid("unsupported CID version"));
+	}
+	read_varint(bytes,pos)?;
+	if read_varint(bytes,pos)? != SHA2_256 || read_varint(bytes,pos)? != 32 {
+		return Err(invalid("unsupported hash function"));
+	}
+	take(bytes,pos,32)
+}
+
+fn write_cbor_head (buf: &mut Vec<u8>, major: u8, n: u64) {
+	let major = major << 5;
+	if n < 24 {
+		buf.push(major | n as u8);
+	}
+	else if n < 1 << 8 {
+		buf.push(major | 24);
+		buf.push(n as u8);
+	}
+	else if n < 1 << 16 {
+		buf.push(major | 25);
+		buf.extend_from_slice(&(n as u16).to_be_bytes());
+	}
+	else if n < 1 << 32 {
+		buf.push(major | 26);
+		buf.extend_from_slice(&(n as u32).to_be_bytes());
+	}
+	else {
+		buf.push(major | 27);
+		buf.extend_from_slice(&n.to_be_bytes());
+	}
+}
+
+fn write_cbor_text (buf: &mut Vec<u8>, s: &str) {
+	write_cbor_head(buf,3,s.len() as u64);
+	buf.extend_from_slice(s.as_bytes());
+}
+
+fn read_cbor_head (bytes: &[u8], pos: &mut usize) -> io::Result<(u8,u64)> {
+	let b = take(bytes,pos,1)?[0];
+	let n = match b & 0x1f {
+		n @ 0..=23	=>	n as u64,
+		24			=>	take(bytes,pos,1)?[0] as u64,
+		25			=>	take(bytes,pos,2)?.iter().fold(0,|n,&x| n << 8 | x as u64),
+		26			=>	take(bytes,pos,4)?.iter().fold(0,|n,&x| n << 8 | x as u64),
+		27			=>	take(bytes,pos,8)?.iter().fold(0,|n,&x| n << 8 | x as u64),
+		_			=>	return Err(invalid("unsupported CBOR item")),
+	};
+	Ok((b >> 5,n))
+}
+
+//reads the roots of a CARv1 header, rejecting fields it does not know of
+fn read_header (bytes: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+	let mut pos = 0;
+	let (major,fields) = read_cbor_head(bytes,&mut pos)?;
+	if major != 5 {
+		return Err(invalid("header is not a map"));
+	}
+	let (mut roots,mut version) = (None,None);
+	for _ in 0..fields {
+		let (major,len) = read_cbor_head(bytes,&mut pos)?;
+		if major != 3 {
+			return Err(invalid("header key is not a string"));
+		}
+		match take(bytes,&mut pos,len as usize)? {
+			b"roots"	=>	{
+				let (major,n) = read_cbor_head(bytes,&mut pos)?;
+				if major != 4 {
+					return Err(invalid("roots are not an array"));
+				}
+				let mut rs = Vec::new();
+				for _ in 0..n {
+					if read_cbor_head(bytes,&mut pos)? != (6,42) {
+						return Err(invalid("root is not a CID"));
+					}
+					let (major,len) = read_cbor_head(bytes,&mut pos)?;
+					let cid = take(bytes,&mut pos,len as usize)?;
+					if major != 2 || cid.first() != Some(&0) {
+						return Err(invalid("root is not a CID"));
+					}
+					rs.push(cid[1..].to_vec());
+				}
+				roots = Some(rs);
+			},
+			b"version"	=>	version = Some(read_cbor_head(bytes,&mut pos)?),
+			_			=>	return Err(invalid("unknown header field")),
+		}
+	}
+	if version != Some((0,1)) {
+		return Err(invalid("unsupported CAR version"));
+	}
+	roots.ok_or(invalid("header lacks roots"))
+}
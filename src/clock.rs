@@ -0,0 +1,47 @@
+use std::fmt::Debug;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// A logical clock used to order the entries of a [log].
+///
+/// The total order of clocks (via [`Ord`]) is used to sort entries, so it must be
+/// consistent with causality: if `a` happened before `b`, then `a < b`.
+/// Entries and logs use [`LamportClock`] by default, [`VectorClock`] can be used
+/// to also tell whether two entries are concurrent, and [`HybridClock`] to order
+/// entries approximately by wall-clock time.
+///
+/// [log]: ../log/struct.Log.html
+/// [`Ord`]: https://doc.rust-lang.org/std/cmp/trait.Ord.html
+/// [`LamportClock`]: ../lamport_clock/struct.LamportClock.html
+/// [`VectorClock`]: ../vector_clock/struct.VectorClock.html
+/// [`HybridClock`]: ../hybrid_clock/struct.HybridClock.html
+pub trait Clock: Clone + Debug + Ord + Serialize + DeserializeOwned + Send + 'static {
+	/// Constructs a new clock with the given identifier.
+	fn new (id: &str) -> Self;
+
+	/// Returns the identifier of the clock.
+	fn id (&self) -> &str;
+
+	/// Returns the current time of the clock as a single scalar.
+	fn time (&self) -> u64;
+
+	/// Advances the time of the clock.
+	fn tick (&mut self);
+
+	/// Merges `o` to `self`, so that `self` is not before `o`. `o` is never modified.
+	fn merge (&mut self, o: &Self);
+
+	/// Tells whether the event timestamped with `self` happened before the one timestamped with `o`.
+	///
+	/// Returns `Some(true)` if it did, `Some(false)` if it did not,
+	/// and `None` if the clock cannot tell.
+	fn happened_before (&self, o: &Self) -> Option<bool>;
+
+	/// Tells whether an entry timestamped with `o`, received from another log,
+	/// is acceptable to a log whose clock is `self`.
+	///
+	/// Returns `true` by default.
+	fn accepts (&self, _o: &Self) -> bool {
+		true
+	}
+}
@@ -0,0 +1,363 @@
+use std::collections::HashMap;
+use std::collections::BTreeMap;
+use serde::{Serialize,Deserialize};
+
+use crate::clock::Clock;
+use crate::entry::Entry;
+use crate::identity::{Identity,Identificator,DefaultIdentificator,Keys};
+use crate::crypto::{LogKey,WrappedKey};
+
+/// A control record, stored in the log as the payload of an entry.
+///
+/// Control records carry metadata about the log itself rather than application data.
+#[derive(Clone,Debug,Eq,PartialEq,Serialize,Deserialize)]
+#[serde(tag = "control",rename_all = "snake_case")]
+pub enum Control {
+	KeyRotation(KeyRotation),
+	Revocation(Revocation),
+	KeyEpoch(KeyEpoch),
+	Checkpoint(Checkpoint),
+}
+
+impl Control {
+	/// Parses the control record carried by `entry`.
+	///
+	/// Returns `None` if the payload of `entry` is not a control record.
+	pub fn from_entry<C: Clock> (entry: &Entry<C>) -> Option<Control> {
+		Control::from_payload(entry.payload())
+	}
+
+	/// Parses the control record serialized into the entry payload `payload`.
+	///
+	/// Returns `None` if `payload` is not a control record.
+	pub fn from_payload (payload: &str) -> Option<Control> {
+		serde_json::from_str(payload).ok()
+	}
+
+	/// Returns the control record serialized into an entry payload.
+	pub fn to_payload (&self) -> String {
+		serde_json::to_string(self).unwrap()
+	}
+
+	/// Returns the control record itself, or the records carried by it if it is a [checkpoint].
+	///
+	/// [checkpoint]: ./struct.Checkpoint.html
+	pub fn records (self) -> Vec<Control> {
+		match self {
+			Control::Checkpoint(c)	=>	c.controls,
+			c						=>	vec![c],
+		}
+	}
+}
+
+/// A signed record linking the key of an old identity to the key of a new one.
+///
+/// Signed by both keys, so that the holder of the old key hands the identity over
+/// and the holder of the new key accepts it.
+#[derive(Clone,Debug,Eq,PartialEq,Serialize,Deserialize)]
+pub struct KeyRotation {
+	old_key: String,
+	new_key: String,
+	old_signature: String,
+	new_signature: String,
+}
+
+impl KeyRotation {
+	/// Constructs a key rotation from the identity `old` to the identity `new`,
+	/// signing it with the keys stored for both in `idp`.
+	///
+	/// Returns `None` if the keys of either identity are not found in `idp`.
+	pub fn new (idp: &dyn Identificator, old: &Identity, new: &Identity) -> Option<KeyRotation> {
+		let msg = KeyRotation::message(old.pub_key(),new.pub_key());
+		let old_signature = idp.sign(&msg,idp.get(old.id())?);
+		let new_signature = idp.sign(&msg,idp.get(new.id())?);
+		Some(KeyRotation {
+			old_key: old.pub_key().to_owned(),
+			new_key: new.pub_key().to_owned(),
+			old_signature: old_signature,
+			new_signature: new_signature,
+		})
+	}
+
+	fn message (old_key: &str, new_key: &str) -> String {
+		format!("rotate:{}:{}",old_key,new_key)
+	}
+
+	/// Returns the public key of the old identity.
+	pub fn old_key (&self) -> &str {
+		&self.old_key
+	}
+
+	/// Returns the public key of the new identity.
+	pub fn new_key (&self) -> &str {
+		&self.new_key
+	}
+
+	/// Returns `true` if the key rotation is signed by both of the keys it links,
+	/// otherwise returns `false`.
+	pub fn verify (&self, idp: &dyn Identificator) -> bool {
+		let msg = KeyRotation::message(&self.old_key,&self.new_key);
+		self.old_key != self.new_key &&
+		idp.verify(&msg,&self.old_signature,&self.old_key) &&
+		idp.verify(&msg,&self.new_signature,&self.new_key)
+	}
+}
+
+/// A signed record revoking a key from the given Lamport clock time onwards.
+///
+/// Entries whose clocks carry the revoked key at a clock time greater than `time` are left out of the log.
+/// A revocation must be signed either by the revoked key itself or by a key
+/// the revoked key has been [rotated] to.
+///
+/// **N.B.** *Entries are not signed and the key in the clock of an entry is set by its writer,
+/// so a revocation is advisory: it is honored by writers keeping to their own keys,
+/// but does not keep the holder of a key from writing under another one.*
+///
+/// [rotated]: ./struct.KeyRotation.html
+#[derive(Clone,Debug,Eq,PartialEq,Serialize,Deserialize)]
+pub struct Revocation {
+	key: String,
+	time: u64,
+	signer: String,
+	signature: String,
+}
+
+impl Revocation {
+	/// Constructs a revocation of the key `key` after the clock time `time`,
+	/// signing it with the keys stored for `signer` in `idp`.
+	///
+	/// Returns `None` if the keys of `signer` are not found in `idp`.
+	pub fn new (idp: &dyn Identificator, signer: &Identity, key: &str, time: u64) -> Option<Revocation> {
+		let msg = Revocation::message(key,time);
+		let signature = idp.sign(&msg,idp.get(signer.id())?);
+		Some(Revocation {
+			key: key.to_owned(),
+			time: time,
+			signer: signer.pub_key().to_owned(),
+			signature: signature,
+		})
+	}
+
+	fn message (key: &str, time: u64) -> String {
+		format!("revoke:{}:{}",key,time)
+	}
+
+	/// Returns the revoked key.
+	pub fn key (&self) -> &str {
+		&self.key
+	}
+
+	/// Returns the clock time after which the key is revoked.
+	pub fn time (&self) -> u64 {
+		self.time
+	}
+
+	/// Returns the public key of the signer.
+	pub fn signer (&self) -> &str {
+		&self.signer
+	}
+
+	/// Returns `true` if the revocation is signed by its signer, otherwise returns `false`.
+	///
+	/// Does not check whether the signer is allowed to revoke the key, see [`KeyRegistry`].
+	///
+	/// [`KeyRegistry`]: ./struct.KeyRegistry.html
+	pub fn verify (&self, idp: &dyn Identificator) -> bool {
+		idp.verify(&Revocation::message(&self.key,self.time),&self.signature,&self.signer)
+	}
+}
+
+/// A key management record distributing the [log key] of a key epoch to its readers.
+///
+/// Holds the [identifier] of the log key and the key wrapped to the public key of each reader.
+/// Several records may exist for the same epoch, e.g. when readers are added without starting
+/// a new epoch, or when new epochs are started concurrently with different keys.
+///
+/// [log key]: ../crypto/struct.LogKey.html
+/// [identifier]: ../crypto/struct.LogKey.html#method.id
+#[derive(Clone,Debug,Eq,PartialEq,Serialize,Deserialize)]
+pub struct KeyEpoch {
+	epoch: u64,
+	#[serde(default)]
+	key: String,
+	keys: BTreeMap<String,WrappedKey>,
+}
+
+impl KeyEpoch {
+	/// Constructs a key management record wrapping `key` to each of the readers
+	/// with the hex-encoded public keys in `readers`.
+	///
+	/// Returns `None` if any of `readers` is not a valid public key.
+	pub fn new (key: &LogKey, readers: &[&str]) -> Option<KeyEpoch> {
+		let mut keys = BTreeMap::new();
+		for r in readers {
+			keys.insert(r.to_string(),key.wrap(r)?);
+		}
+		Some(KeyEpoch {
+			epoch: key.epoch(),
+			key: key.id(),
+			keys: keys,
+		})
+	}
+
+	/// Returns the key epoch.
+	pub fn epoch (&self) -> u64 {
+		self.epoch
+	}
+
+	/// Returns the identifier of the log key, or an empty string for records
+	/// made before keys had identifiers.
+	pub fn key_id (&self) -> &str {
+		&self.key
+	}
+
+	/// Returns the public keys of the readers.
+	pub fn readers (&self) -> Vec<&str> {
+		self.keys.keys().map(|x| &x[..]).collect()
+	}
+
+	/// Unwraps the log key of the epoch for the reader owning `keys`.
+	///
+	/// Returns `None` if the log key was not wrapped to the reader.
+	pub fn unwrap_key (&self, keys: &Keys) -> Option<LogKey> {
+		LogKey::from_wrapped(self.keys.get(keys.pub_key())?,keys.sec_key(),keys.pub_key(),self.epoch)
+	}
+}
+
+/// A checkpoint of the state of a log, after which the entries causally before it may be pruned.
+///
+/// Carries the state of the application at the checkpoint and the control records of
+/// the log up to it, so that key rotations, revocations and key epochs outlive the pruned entries.
+#[derive(Clone,Debug,Eq,PartialEq,Serialize,Deserialize)]
+pub struct Checkpoint {
+	state: String,
+	controls: Vec<Control>,
+}
+
+impl Checkpoint {
+	/// Constructs a checkpoint of the state `state`, carrying the control records `controls`.
+	/// The records carried by checkpoints among `controls` are carried in their stead.
+	pub fn new (state: &str, controls: Vec<Control>) -> Checkpoint {
+		Checkpoint {
+			state: state.to_owned(),
+			controls: controls.into_iter().flat_map(Control::records).collect(),
+		}
+	}
+
+	/// Returns the state at the checkpoint.
+	pub fn state (&self) -> &str {
+		&self.state
+	}
+
+	/// Returns the control records carried by the checkpoint.
+	pub fn controls (&self) -> &[Control] {
+		&self.controls
+	}
+}
+
+/// A registry of the verified key rotations and revocations of a log.
+///
+/// The registry does not depend on the order in which records are registered,
+/// so replicas registering the same records agree on the keys in effect.
+pub struct KeyRegistry {
+	verifier: DefaultIdentificator,
+	successors: HashMap<String,String>,
+	revocations: Vec<Revocation>,
+}
+
+impl KeyRegistry {
+	/// Constructs an empty key registry.
+	pub fn new () -> KeyRegistry {
+		KeyRegistry {
+			verifier: DefaultIdentificator::new(),
+			successors: HashMap::new(),
+			revocations: Vec::new(),
+		}
+	}
+
+	/// Registers the control records carried by `entries`, including those carried
+	/// by checkpoints, ignoring all the records that do not pass verification.
+	pub fn register<'a,C,I> (&mut self, entries: I)
+	where C: Clock, I: IntoIterator<Item = &'a Entry<C>> {
+		for c in entries.into_iter().filter_map(Control::from_entry).flat_map(Control::records) {
+			match c {
+				Control::KeyRotation(r)	=>	{
+					self.rotate(&r);
+				},
+				Control::Revocation(r)	=>	{
+					self.revoke(&r);
+				},
+				_						=>	(),
+			}
+		}
+	}
+
+	/// Registers the key rotation `rotation` if it passes verification.
+	///
+	/// Of several rotations of the same key, the one to the lowest new key is in effect,
+	/// so that replicas agree on it whatever order they register the rotations in.
+	///
+	/// Returns `true` if the key rotation is in effect, otherwise returns `false`.
+	pub fn rotate (&mut self, rotation: &KeyRotation) -> bool {
+		if !rotation.verify(&self.verifier) {
+			return false;
+		}
+		let s = self.successors.entry(rotation.old_key().to_owned()).or_insert(rotation.new_key().to_owned());
+		if rotation.new_key() < &s[..] {
+			*s = rotation.new_key().to_owned();
+		}
+		s == rotation.new_key()
+	}
+
+	/// Registers the revocation `revocation` if it passes verification.
+	///
+	/// A revocation is in effect while its signer is allowed to revoke the key,
+	/// which may depend on key rotations registered after it. Of several revocations
+	/// of the same key in effect, the one with the earliest clock time counts.
+	///
+	/// Returns `true` if the revocation is in effect, otherwise returns `false`.
+	pub fn revoke (&mut self, revocation: &Revocation) -> bool {
+		if !revocation.verify(&self.verifier) {
+			return false;
+		}
+		if !self.revocations.contains(revocation) {
+			self.revocations.push(revocation.clone());
+		}
+		self.is_successor(revocation.key(),revocation.signer())
+	}
+
+	/// Returns `true` if `key` is `successor` or has been rotated to it,
+	/// either directly or through intermediate keys. Otherwise returns `false`.
+	pub fn is_successor (&self, key: &str, successor: &str) -> bool {
+		let mut k = key;
+		let mut steps = 0;
+		while k != successor {
+			match self.successors.get(k) {
+				Some(s) if steps < self.successors.len()	=>	k = s,
+				_											=>	return false,
+			}
+			steps += 1;
+		}
+		true
+	}
+
+	/// Returns the most recent key `key` has been rotated to, or `key` itself if it has not been rotated.
+	pub fn current<'a> (&'a self, key: &'a str) -> &'a str {
+		let mut k = key;
+		let mut steps = 0;
+		while let Some(s) = self.successors.get(k) {
+			if steps >= self.successors.len() {
+				break;
+			}
+			k = s;
+			steps += 1;
+		}
+		k
+	}
+
+	/// Returns `true` if `key` is revoked at the clock time `time`, otherwise returns `false`.
+	pub fn is_revoked (&self, key: &str, time: u64) -> bool {
+		self.revocations.iter().filter(|r| r.key() == key && self.is_successor(key,r.signer())).
+		map(|r| r.time()).min().map(|t| time > t).unwrap_or(false)
+	}
+}
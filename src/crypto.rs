@@ -0,0 +1,253 @@
+use serde::{Serialize,Deserialize};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use sha2::{Sha256,Digest};
+use secp256k1::{Secp256k1,PublicKey,SecretKey};
+use secp256k1::ecdh::SharedSecret;
+use chacha20poly1305::{XChaCha20Poly1305,Key,XNonce};
+use chacha20poly1305::aead::{Aead,NewAead,Payload};
+use hex;
+
+use crate::control::Control;
+
+/// A symmetric key shared by the readers of a [log], used to encrypt entry payloads
+/// with [XChaCha20-Poly1305].
+///
+/// Each log key belongs to a key epoch. A new epoch starts with a new key whenever readers
+/// are removed from the log, so that they cannot decrypt the entries appended after that.
+///
+/// [log]: ../log/struct.Log.html
+/// [XChaCha20-Poly1305]: https://tools.ietf.org/html/draft-arciszewski-xchacha-03
+#[derive(Clone,Eq,PartialEq)]
+pub struct LogKey {
+	key: [u8; 32],
+	epoch: u64,
+}
+
+impl LogKey {
+	/// Constructs a log key of the epoch 0 from the 32 bytes in `key`.
+	pub fn new (key: [u8; 32]) -> LogKey {
+		LogKey {
+			key: key,
+			epoch: 0,
+		}
+	}
+
+	/// Sets the epoch of the (newly constructed) log key.
+	///
+	/// ```ignore
+	/// let key = LogKey::generate().set_epoch(2);
+	/// ```
+	pub fn set_epoch (mut self, epoch: u64) -> LogKey {
+		self.epoch = epoch;
+		self
+	}
+
+	/// Returns the epoch of the log key.
+	pub fn epoch (&self) -> u64 {
+		self.epoch
+	}
+
+	/// Returns the identifier of the log key, the hex-encoded first 8 bytes of the SHA-256 digest of the key.
+	///
+	/// Tells apart different keys of the same epoch, e.g. keys of epochs started concurrently on several replicas.
+	pub fn id (&self) -> String {
+		hex::encode(&Sha256::digest(&self.key)[..8])
+	}
+
+	/// Constructs a new, random log key.
+	pub fn generate () -> LogKey {
+		let mut key = [0u8; 32];
+		OsRng::new().unwrap().fill_bytes(&mut key);
+		LogKey::new(key)
+	}
+
+	/// Constructs a log key from its hex-encoded representation `key`.
+	///
+	/// Returns `None` if `key` is not a hex-encoded 32-byte key.
+	pub fn from_hex (key: &str) -> Option<LogKey> {
+		let bytes = hex::decode(key).ok()?;
+		if bytes.len() != 32 {
+			return None;
+		}
+		let mut key = [0u8; 32];
+		key.copy_from_slice(&bytes);
+		Some(LogKey::new(key))
+	}
+
+	/// Returns the hex-encoded representation of the key.
+	pub fn to_hex (&self) -> String {
+		hex::encode(&self.key)
+	}
+
+	/// Returns the bytes of the key.
+	pub fn bytes (&self) -> &[u8] {
+		&self.key
+	}
+
+	/// Encrypts `data`, authenticating it together with `aad`.
+	///
+	/// Returns the ciphertext as a [sealed payload].
+	///
+	/// [sealed payload]: ./struct.Sealed.html
+	pub fn seal (&self, data: &str, aad: &str) -> String {
+		let mut nonce = [0u8; 24];
+		OsRng::new().unwrap().fill_bytes(&mut nonce);
+		let cipher = XChaCha20Poly1305::new(&Key::from(self.key));
+		let ct = cipher.encrypt(&XNonce::from(nonce),Payload {
+			msg: data.as_bytes(),
+			aad: aad.as_bytes(),
+		}).unwrap();
+		Sealed {
+			sealed: 1,
+			epoch: self.epoch,
+			key: self.id(),
+			nonce: hex::encode(&nonce[..]),
+			data: hex::encode(&ct),
+		}.to_payload()
+	}
+
+	/// Decrypts the [sealed payload] `payload`, authenticating it together with `aad`.
+	///
+	/// Returns `None` if `payload` is not a sealed payload,
+	/// or it was not sealed with this key and `aad`.
+	///
+	/// [sealed payload]: ./struct.Sealed.html
+	pub fn open (&self, payload: &str, aad: &str) -> Option<String> {
+		let sealed = Sealed::from_payload(payload)?;
+		if sealed.epoch != self.epoch || !sealed.key.is_empty() && sealed.key != self.id() {
+			return None;
+		}
+		let bytes = hex::decode(&sealed.nonce).ok()?;
+		if bytes.len() != 24 {
+			return None;
+		}
+		let mut nonce = [0u8; 24];
+		nonce.copy_from_slice(&bytes);
+		let cipher = XChaCha20Poly1305::new(&Key::from(self.key));
+		let pt = cipher.decrypt(&XNonce::from(nonce),Payload {
+			msg: &hex::decode(&sealed.data).ok()?,
+			aad: aad.as_bytes(),
+		}).ok()?;
+		String::from_utf8(pt).ok()
+	}
+
+	/// Wraps the log key to the reader with the hex-encoded public key `pk`,
+	/// using [ECIES] with an ephemeral secp256k1 key.
+	///
+	/// Returns `None` if `pk` is not a valid public key.
+	///
+	/// [ECIES]: https://en.wikipedia.org/wiki/Integrated_Encryption_Scheme
+	pub fn wrap (&self, pk: &str) -> Option<WrappedKey> {
+		let secp = Secp256k1::new();
+		let public_key = PublicKey::from_slice(&hex::decode(pk).ok()?).ok()?;
+		let (ephemeral_key,ephemeral) = secp.generate_keypair(&mut OsRng::new().unwrap());
+		let wrapping_key = LogKey::derive(&SharedSecret::new(&public_key,&ephemeral_key)[..],&ephemeral);
+		Some(WrappedKey {
+			ephemeral: hex::encode(&ephemeral.serialize()[..]),
+			key: wrapping_key.seal(&self.to_hex(),pk),
+		})
+	}
+
+	/// Unwraps the log key of the epoch `epoch` from `wrapped`, using the hex-encoded
+	/// secret key `sk` of the reader with the hex-encoded public key `pk`.
+	///
+	/// Returns `None` if the key was not wrapped to the reader.
+	pub fn from_wrapped (wrapped: &WrappedKey, sk: &str, pk: &str, epoch: u64) -> Option<LogKey> {
+		let secret_key = SecretKey::from_slice(&hex::decode(sk).ok()?).ok()?;
+		let ephemeral = PublicKey::from_slice(&hex::decode(&wrapped.ephemeral).ok()?).ok()?;
+		let wrapping_key = LogKey::derive(&SharedSecret::new(&ephemeral,&secret_key)[..],&ephemeral);
+		Some(LogKey::from_hex(&wrapping_key.open(&wrapped.key,pk)?)?.set_epoch(epoch))
+	}
+
+	fn derive (shared: &[u8], ephemeral: &PublicKey) -> LogKey {
+		let mut hasher = Sha256::new();
+		hasher.input(shared);
+		hasher.input(&ephemeral.serialize()[..]);
+		let mut key = [0u8; 32];
+		key.copy_from_slice(&hasher.result());
+		LogKey::new(key)
+	}
+}
+
+/// A log key wrapped to a single reader, consisting of the ephemeral public key
+/// and the encrypted log key.
+#[derive(Clone,Debug,Eq,PartialEq,Serialize,Deserialize)]
+pub struct WrappedKey {
+	ephemeral: String,
+	key: String,
+}
+
+/// An encrypted entry payload, consisting of the epoch and the identifier of the key
+/// it was sealed with, the nonce and the ciphertext.
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub struct Sealed {
+	sealed: u32,
+	#[serde(default)]
+	epoch: u64,
+	#[serde(default)]
+	key: String,
+	nonce: String,
+	data: String,
+}
+
+impl Sealed {
+	/// Parses a sealed payload from the entry payload `payload`.
+	///
+	/// Returns `None` if `payload` is not a sealed payload.
+	pub fn from_payload (payload: &str) -> Option<Sealed> {
+		serde_json::from_str(payload).ok()
+	}
+
+	/// Returns the sealed payload serialized into an entry payload.
+	pub fn to_payload (&self) -> String {
+		serde_json::to_string(self).unwrap()
+	}
+
+	/// Returns the epoch of the key the payload was sealed with.
+	pub fn epoch (&self) -> u64 {
+		self.epoch
+	}
+
+	/// Returns the identifier of the key the payload was sealed with,
+	/// or an empty string for payloads sealed before keys had identifiers.
+	pub fn key_id (&self) -> &str {
+		&self.key
+	}
+
+	/// Returns `true` if `payload` is a sealed payload, otherwise returns `false`.
+	pub fn is_sealed (payload: &str) -> bool {
+		Sealed::from_payload(payload).is_some()
+	}
+
+	/// Returns the plaintext `data` as an entry payload, escaping it if it would be taken
+	/// for a reserved payload, i.e. a sealed payload, an escaped one or a [control record],
+	/// so that plaintext payloads are never mistaken for sealed payloads or records of the log.
+	///
+	/// [control record]: ../control/enum.Control.html
+	pub fn escape (data: &str) -> String {
+		if Sealed::is_sealed(data) || Sealed::unescape(data).is_some() || Control::from_payload(data).is_some() {
+			serde_json::to_string(&Escaped {
+				plain: data.to_owned(),
+			}).unwrap()
+		}
+		else {
+			data.to_owned()
+		}
+	}
+
+	/// Returns the plaintext escaped in the entry payload `payload` by [`escape`].
+	///
+	/// Returns `None` if `payload` is not an escaped payload.
+	///
+	/// [`escape`]: #method.escape
+	pub fn unescape (payload: &str) -> Option<String> {
+		serde_json::from_str::<Escaped>(payload).ok().map(|x| x.plain)
+	}
+}
+
+//a plaintext payload escaped by Sealed::escape
+#[derive(Serialize,Deserialize)]
+struct Escaped {
+	plain: String,
+}
@@ -1,43 +1,47 @@
 use std::cmp::Ordering;
 use std::rc::Rc;
-use std::sync::Arc;
-use std::sync::Mutex;
+use std::collections::HashSet;
 use serde_json::json;
 use serde::{Serialize,Deserialize};
 
-use std::io::Cursor;
-use futures::{future::{Future,join_all},stream::Stream};
+use futures::future::{Future,join_all};
 use tokio::runtime::Runtime;
-use ipfs_api::{IpfsClient,response::Error};
 
+use crate::clock::Clock;
 use crate::lamport_clock::LamportClock;
 use crate::identity::Identity;
+use crate::store::{Store,StoreFuture,StoreError};
 
 /// A wrapper containing either a reference to an entry
 /// or a hash as a string.
-pub enum EntryOrHash<'a> {
-	Entry(&'a Entry),
+pub enum EntryOrHash<'a,C = LamportClock> {
+	Entry(&'a Entry<C>),
 	Hash(String),
 }
 
 /// An entry containing data payload, a hash to locate it in [`IPFS`],
 /// and pointers to its parents.
 ///
+/// Entries are timestamped with a [Lamport clock] by default,
+/// any other [clock] can be used via the type parameter `C`.
+///
 /// [`IPFS`]: https://ipfs.io
+/// [Lamport clock]: ../lamport_clock/struct.LamportClock.html
+/// [clock]: ../clock/trait.Clock.html
 #[derive(Clone,Debug,Serialize,Deserialize)]
-pub struct Entry {
+pub struct Entry<C = LamportClock> {
 	hash: String,
 	id: String,
 	payload: String,
 	next: Vec<String>,
 	v: u32,
-	clock: LamportClock,
+	clock: C,
 }
 
-impl Entry {
+impl<C: Clock> Entry<C> {
 	//very ad hoc
 	#[doc(hidden)]
-	pub fn empty () -> Entry {
+	pub fn empty () -> Entry<C> {
 		let s = "0000";
 		Entry {
 			hash: s.to_owned(),
@@ -45,18 +49,20 @@ impl Entry {
 			payload: s.to_owned(),
 			next: Vec::new(),
 			v: 0,
-			clock: LamportClock::new(s),
+			clock: C::new(s),
 		}
 	}
 
 	#[doc(hidden)]
 	pub fn new (identity: Identity, log_id: &str, data: &str,
-	next: &[EntryOrHash], clock: Option<LamportClock>) -> Entry {
-		//None filtering required?
-		let next = next.iter().map(|n| match n {
+	next: &[EntryOrHash<C>], clock: Option<C>) -> Entry<C> {
+		//an entry points to each of its parents once
+		let mut next = next.iter().map(|n| match n {
 			EntryOrHash::Entry(e)	=>	e.hash.to_owned(),
 			EntryOrHash::Hash(h)	=>	h.to_owned(),
-		}).collect();
+		}).collect::<Vec<_>>();
+		let mut seen = HashSet::new();
+		next.retain(|x| seen.insert(x.to_owned()));
 		Entry {
 			//very much ad hoc
 			hash: data.to_owned(),
@@ -64,33 +70,35 @@ impl Entry {
 			payload: data.to_owned(),
 			next: next,
 			v: 1,
-			clock: clock.unwrap_or(LamportClock::new(identity.pub_key())),
+			clock: clock.unwrap_or(C::new(identity.pub_key())),
 		}
 	}
 
 	/// Locally creates an entry owned by `identity` .
 	///
 	///  The created entry is part of the [log] with the id `log_id`,
-	/// holds payload of `data` and can be assigned to point to
-	/// at most two parents with their hashes in `nexts`. Providing a
-	/// [Lamport clock] via `clock` is optional.
+	/// holds payload of `data` and points to any number of parents,
+	/// given as entries or hashes in `nexts`, each of them once. Providing a
+	/// [clock] via `clock` is optional.
 	///
 	/// Returns a [reference-counting pointer] to the created entry.
 	///
 	/// [log]: ../log/struct.Log.html
-	/// [Lamport clock]: ../lamport_clock/struct.LamportClock.html
+	/// [clock]: ../clock/trait.Clock.html
 	/// [reference-counting pointer]: https://doc.rust-lang.org/std/rc/struct.Rc.html
-	pub fn create (ipfs: &IpfsClient, identity: Identity, log_id: &str, data: &str,
-	nexts: &[EntryOrHash], clock: Option<LamportClock>) -> Rc<Entry> {
+	pub fn create<S: Store + ?Sized> (store: &S, identity: Identity, log_id: &str, data: &str,
+	nexts: &[EntryOrHash<C>], clock: Option<C>) -> Rc<Entry<C>> {
 		let mut e = Entry::new(identity,log_id,data,nexts,clock);
-		e.hash = Runtime::new().unwrap().block_on(Entry::multihash(ipfs,&e)).unwrap();
+		e.hash = Runtime::new().unwrap().block_on(Entry::multihash(store,&e)).unwrap();
 		Rc::new(e)
 	}
 
-	/// Stores `entry` in the IPFS client `ipfs` and returns a future containing its multihash.
+	/// Stores `entry` in the [store] `store` and returns a future containing its multihash.
 	///
 	/// **N.B.** *At the moment stores the entry as JSON, not CBOR DAG.*
-	pub fn multihash (ipfs: &IpfsClient, entry: &Entry) -> impl Future<Item = String,Error = Error> + Send {
+	///
+	/// [store]: ../store/trait.Store.html
+	pub fn multihash<S: Store + ?Sized> (store: &S, entry: &Entry<C>) -> StoreFuture<String> {
 		let e = json!({
 			"hash": "null",
 			"id": entry.id,
@@ -99,43 +107,81 @@ impl Entry {
 			"v": entry.v,
 			"clock": entry.clock,
 		}).to_string();
-		ipfs.add(Cursor::new(e)).map(|x| x.hash)
+		store.add(e.into_bytes())
 	}
 
-	/// Returns the future containing the entry stored in the IPFS client `ipfs` with the multihash `hash`.
+	/// Returns the future containing the entry stored in the [store] `store` with the multihash `hash`.
+	///
+	/// The future fails with [`StoreError::Invalid`] if the block is not an entry.
 	///
 	/// **N.B.** *At the moment converts the entry from JSON, not CBOR DAG.*
-	pub fn from_multihash (ipfs: &IpfsClient, hash: &str) -> impl Future<Item = Entry,Error = Error> + Send {
+	///
+	/// [store]: ../store/trait.Store.html
+	/// [`StoreError::Invalid`]: ../store/enum.StoreError.html#variant.Invalid
+	pub fn from_multihash<S: Store + ?Sized> (store: &S, hash: &str) -> StoreFuture<Entry<C>> {
 		let h = hash.to_owned();
-		ipfs.cat(hash).concat2().map(|x| {
-			let mut e: Entry = serde_json::from_str(std::str::from_utf8(&x).unwrap()).unwrap();
+		Box::new(store.cat(hash).and_then(|x| {
+			let mut e: Entry<C> = serde_json::from_slice(&x).map_err(|e| StoreError::Invalid(format!("block {} is not an entry: {}",h,e)))?;
 			e.hash = h;
-			e
-		})
+			Ok(e)
+		}))
+	}
+
+	/// Fetches all the entries with the hashes in `hashes` and all their parents from the [store] `store`.
+	/// Entries that cannot be fetched or are not entries are skipped.
+	///
+	/// Returns a vector of entries.
+	///
+	/// [store]: ../store/trait.Store.html
+	pub fn fetch_entries<S: Store + ?Sized> (store: &S, hashes: &[String]) -> Vec<Entry<C>> {
+		Entry::fetch_entries_limited(store,hashes,None,&HashSet::new())
 	}
 
-	/// Fetches all the entries with the hashes in `hashes` and all their parents from the IPFS client `ipfs`.
+	/// Fetches the entries with the hashes in `hashes` and their parents from the [store] `store`,
+	/// breadth first, until `amount` entries have been fetched. Entries with the hashes in `known`
+	/// are neither fetched nor followed to their parents.
+	///
+	/// Fetches all the entries if `amount` is `None`.
 	///
 	/// Returns a vector of entries.
-	pub fn fetch_entries (ipfs: &IpfsClient, hashes: &[String]) -> Vec<Entry> {
-		let hashes = Arc::new(Mutex::new(hashes.to_vec()));
+	///
+	/// [store]: ../store/trait.Store.html
+	pub fn fetch_entries_limited<S: Store + ?Sized> (store: &S, hashes: &[String], amount: Option<usize>,
+	known: &HashSet<String>) -> Vec<Entry<C>> {
+		Entry::fetch_entries_until(store,hashes,amount,known,|_| false)
+	}
+
+	/// Fetches entries as [`fetch_entries_limited`] does, except that the entries
+	/// for which `stop` returns `true` are not followed to their parents.
+	/// `stop` is called once for each entry fetched, in the order the entries are fetched.
+	///
+	/// Returns a vector of entries.
+	///
+	/// [`fetch_entries_limited`]: #method.fetch_entries_limited
+	pub fn fetch_entries_until<S,F> (store: &S, hashes: &[String], amount: Option<usize>,
+	known: &HashSet<String>, stop: F) -> Vec<Entry<C>>
+	where S: Store + ?Sized, F: Fn(&Entry<C>) -> bool {
+		let mut seen = known.clone();
+		let mut hashes = hashes.iter().filter(|&x| seen.insert(x.to_owned())).map(|x| x.to_owned()).collect::<Vec<_>>();
 		let mut es = Vec::new();
-		loop {
-			let mut result = Vec::new();
-			while !hashes.lock().unwrap().is_empty() {
-				let h = hashes.lock().unwrap().remove(0);
-				let hashes_clone = hashes.clone();
-				result.push(Entry::from_multihash(ipfs,&h).
-				map(move |x| {
-					for n in &x.next {
-						hashes_clone.lock().unwrap().push(n.to_owned());
+		while !hashes.is_empty() {
+			if let Some(n) = amount {
+				hashes.truncate(n - es.len());
+			}
+			let result = hashes.iter().map(|h| Entry::<C>::from_multihash(store,h)).collect::<Vec<_>>();
+			//a missing or malformed entry does not fail the others
+			let result = result.into_iter().map(|x| x.then(|x| Ok::<_,StoreError>(x.ok())));
+			let fetched = Runtime::new().unwrap().block_on(join_all(result)).unwrap().into_iter().flatten().collect::<Vec<_>>();
+			hashes = Vec::new();
+			for e in fetched.iter().filter(|&e| !stop(e)) {
+				for n in &e.next {
+					if seen.insert(n.to_owned()) {
+						hashes.push(n.to_owned());
 					}
-					x
-				}));
+				}
 			}
-			es = es.into_iter().chain(Runtime::new().unwrap().block_on(join_all(result)).
-			unwrap().into_iter()).collect::<Vec<Entry>>();
-			if hashes.lock().unwrap().is_empty() {
+			es.extend(fetched);
+			if amount.map(|n| es.len() >= n).unwrap_or(false) {
 				break;
 			}
 		}
@@ -162,27 +208,26 @@ impl Entry {
 		&self.payload
 	}
 
-	/// Returns the hashes of the parents.
+	/// Returns the hashes of the parents, each of them once.
 	///
-	/// The length of the returned slice is either:
-	/// * 0 &mdash; no parents
-	/// * 2 &mdash; two identical strings for one parent, two distinct strings for two different parents
+	/// Entries created by earlier versions may list the same parent twice,
+	/// e.g. two identical strings for one parent.
 	pub fn next (&self) -> &[String] {
 		&self.next
 	}
 
-	/// Returns the Lamport clock of the entry.
-	pub fn clock (&self) -> &LamportClock {
+	/// Returns the clock of the entry.
+	pub fn clock (&self) -> &C {
 		&self.clock
 	}
 
 	/// Returns `true` if `e1` is the parent of `e2`, otherwise returns `false`.
-	pub fn is_parent (e1: &Entry, e2: &Entry) -> bool {
+	pub fn is_parent (e1: &Entry<C>, e2: &Entry<C>) -> bool {
 		e2.next().iter().any(|x| x == e1.hash())
 	}
 
 	/// Returns a vector of pointers to all direct and indirect children of `entry` in `entries`.
-	pub fn find_children (entry: &Entry, entries: &[Rc<Entry>]) -> Vec<Rc<Entry>> {
+	pub fn find_children (entry: &Entry<C>, entries: &[Rc<Entry<C>>]) -> Vec<Rc<Entry<C>>> {
 		let mut stack = Vec::new();
 		let mut parent = entries.iter().find(|e| Entry::is_parent(entry,e));
 		while let Some(p) = parent {
@@ -201,7 +246,7 @@ impl Entry {
 	/// Returns an ordering.
 	///
 	/// [`sort_step_by_step`]: #method.sort_step_by_step
-	pub fn last_write_wins (a: &Entry, b: &Entry) -> Ordering {
+	pub fn last_write_wins (a: &Entry<C>, b: &Entry<C>) -> Ordering {
 		Entry::sort_step_by_step(|_,_| Ordering::Less)(a,b)
 	}
 
@@ -212,27 +257,50 @@ impl Entry {
 	/// Returns an ordering.
 	///
 	/// [`sort_step_by_step`]: #method.sort_step_by_step
-	pub fn sort_by_entry_hash (a: &Entry, b: &Entry) -> Ordering {
+	pub fn sort_by_entry_hash (a: &Entry<C>, b: &Entry<C>) -> Ordering {
 		Entry::sort_step_by_step(|a,b| a.hash().cmp(&b.hash()))(a,b)
 	}
 
+	/// A sorting helper function to put the entry written by the writer with the higher priority
+	/// after the other, whatever their clock times.
+	///
+	/// `priorities` lists the clock identifiers of the writers, highest priority first.
+	/// Writers not listed have the lowest priority. Entries of writers with equal priorities
+	/// are sorted by their clocks and then by their hashes.
+	///
+	/// As the [log] never lists an entry before its parents, only concurrent entries,
+	/// i.e. entries neither of which is in the causal past of the other, are ordered by priority.
+	///
+	/// Returns a closure that can be used as a sorting function.
+	///
+	/// [log]: ../log/struct.Log.html
+	pub fn sort_by_writer_priority (priorities: Vec<String>) -> impl Fn(&Entry<C>,&Entry<C>) -> Ordering {
+		move |a,b| {
+			let rank = |e: &Entry<C>| priorities.iter().position(|x| x == e.clock().id()).
+			map(|i| priorities.len() - i).unwrap_or(0);
+			rank(a).cmp(&rank(b)).
+			then_with(|| a.clock().cmp(b.clock())).
+			then_with(|| a.hash().cmp(b.hash()))
+		}
+	}
+
 	/// A sorting helper function to
 	/// 1. first try to sort the two entries using `resolve`,
-	/// 2. if still unsorted (equal), try to sort based on the Lamport clock identifiers of the respective entries,
-	/// 3. sort by the Lamport clocks of the respective entries.
+	/// 2. if still unsorted (equal), try to sort based on the clock identifiers of the respective entries,
+	/// 3. sort by the clocks of the respective entries.
 	///
 	/// Returns a closure that can be used as a sorting function.
-	pub fn sort_step_by_step<F> (resolve: F) -> impl Fn(&Entry,&Entry) -> Ordering
-	where F: 'static + Fn(&Entry,&Entry) -> Ordering {
+	pub fn sort_step_by_step<F> (resolve: F) -> impl Fn(&Entry<C>,&Entry<C>) -> Ordering
+	where F: 'static + Fn(&Entry<C>,&Entry<C>) -> Ordering {
 		Entry::sort_by_clocks(Entry::sort_by_clock_ids(resolve))
 	}
 
-	/// A sorting helper function to sort by the Lamport clocks of the respective entries.
-	/// In the case the Lamport clocks are equal, tries to sort using `resolve`.
+	/// A sorting helper function to sort by the clocks of the respective entries.
+	/// In the case the clocks are equal, tries to sort using `resolve`.
 	///
 	/// Returns a closure that can be used as a sorting function.
-	pub fn sort_by_clocks<F> (resolve: F) -> impl Fn(&Entry,&Entry) -> Ordering
-	where F: 'static + Fn(&Entry,&Entry) -> Ordering {
+	pub fn sort_by_clocks<F> (resolve: F) -> impl Fn(&Entry<C>,&Entry<C>) -> Ordering
+	where F: 'static + Fn(&Entry<C>,&Entry<C>) -> Ordering {
 		move |a,b| {
 			let mut diff = a.clock().cmp(&b.clock());
 			if diff == Ordering::Equal {
@@ -242,12 +310,12 @@ impl Entry {
 		}
 	}
 
-	/// A sorting helper function to sort by the Lamport clock identifiers of the respective entries.
-	/// In the case the Lamport clocks identifiers are equal, tries to sort using `resolve`.
+	/// A sorting helper function to sort by the clock identifiers of the respective entries.
+	/// In the case the clock identifiers are equal, tries to sort using `resolve`.
 	///
 	/// Returns a closure that can be used as a sorting function.
-	pub fn sort_by_clock_ids<F> (resolve: F) -> impl Fn(&Entry,&Entry) -> Ordering
-	where F: 'static + Fn(&Entry,&Entry) -> Ordering {
+	pub fn sort_by_clock_ids<F> (resolve: F) -> impl Fn(&Entry<C>,&Entry<C>) -> Ordering
+	where F: 'static + Fn(&Entry<C>,&Entry<C>) -> Ordering {
 		move |a,b| {
 			let mut diff = a.clock().id().cmp(&b.clock().id());
 			if diff == Ordering::Equal {
@@ -262,8 +330,8 @@ impl Entry {
 	///
 	/// Returns a closure that behaves in the same way as `fn_sort`
 	/// but panics if the two entries given as input are equal.
-	pub fn no_zeroes<F> (fn_sort: F) -> impl Fn(&Entry,&Entry) -> Ordering
-	where F: 'static + Fn(&Entry,&Entry) -> Ordering {
+	pub fn no_zeroes<F> (fn_sort: F) -> impl Fn(&Entry<C>,&Entry<C>) -> Ordering
+	where F: 'static + Fn(&Entry<C>,&Entry<C>) -> Ordering {
 		move |a,b| {
 			let diff = fn_sort(a,b);
 			if diff == Ordering::Equal {
@@ -275,15 +343,15 @@ impl Entry {
 	}
 }
 
-impl PartialEq for Entry {
+impl<C: Clock> PartialEq for Entry<C> {
 	fn eq (&self, other: &Self) -> bool {
 		self.hash == other.hash
 	}
 }
 
-impl Eq for Entry {}
+impl<C: Clock> Eq for Entry<C> {}
 
-impl Ord for Entry {
+impl<C: Clock> Ord for Entry<C> {
 	fn cmp (&self, other: &Self) -> Ordering {
 		let diff = self.clock().cmp(other.clock());
 		if diff == Ordering::Equal {
@@ -295,7 +363,7 @@ impl Ord for Entry {
 	}
 }
 
-impl PartialOrd for Entry {
+impl<C: Clock> PartialOrd for Entry<C> {
 	fn partial_cmp (&self, other: &Self) -> Option<Ordering> {
 		Some(self.cmp(other))
 	}
@@ -0,0 +1,41 @@
+use std::rc::Rc;
+
+use crate::entry::Entry;
+use crate::lamport_clock::LamportClock;
+
+/// An event emitted by a [log] to its subscribers.
+///
+/// Subscribe to the events of a log with [`Log::subscribe`],
+/// or with [`LogOptions::subscribe`] to also receive the events emitted while it is constructed.
+///
+/// [log]: ../log/struct.Log.html
+/// [`Log::subscribe`]: ../log/struct.Log.html#method.subscribe
+/// [`LogOptions::subscribe`]: ../log/struct.LogOptions.html#method.subscribe
+#[derive(Clone,Debug)]
+pub enum Event<C = LamportClock> {
+	/// The entry was appended to the log.
+	Appended(Rc<Entry<C>>),
+	/// Entries were joined into the log from another log.
+	Joined {
+		/// The entries new to the log.
+		new_entries: Vec<Rc<Entry<C>>>,
+		/// The heads of the log after joining.
+		new_heads: Vec<Rc<Entry<C>>>,
+	},
+	/// Entries are being fetched from the store.
+	LoadProgress {
+		/// The number of entries fetched so far.
+		loaded: usize,
+		/// The maximum number of entries to fetch, if known.
+		total_hint: Option<usize>,
+	},
+	/// Entries were removed from the log, by truncating it when joining, by [pruning] it,
+	/// or because the keys in their clocks were [revoked].
+	///
+	/// [pruning]: ../log/struct.Log.html#method.prune
+	/// [revoked]: ../control/struct.Revocation.html
+	Truncated {
+		/// The hashes of the removed entries.
+		removed: Vec<String>,
+	},
+}
@@ -0,0 +1,161 @@
+use serde::{Serialize,Deserialize};
+use std::cmp::Ordering;
+use std::time::SystemTime;
+
+use crate::clock::Clock;
+
+/// A [hybrid logical clock] combining wall-clock time with a logical counter.
+///
+/// Orders events approximately by wall-clock time while staying consistent with causality:
+/// when the wall clocks of the participants are in sync, the greater of two hybrid clocks
+/// is the one ticked later in real time.
+///
+/// [hybrid logical clock]: https://cse.buffalo.edu/tech-reports/2014-04.pdf
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub struct HybridClock {
+	id: String,
+	wall: u64,
+	logical: u64,
+}
+
+impl HybridClock {
+	/// The maximum number of milliseconds a clock received from another log
+	/// may be ahead of the local wall clock for it to be [accepted].
+	///
+	/// [accepted]: ../clock/trait.Clock.html#method.accepts
+	pub const MAX_DRIFT: u64 = 60_000;
+
+	/// Constructs a new hybrid logical clock with the given identifier.
+	pub fn new (id: &str) -> HybridClock {
+		HybridClock {
+			id: id.to_owned(),
+			wall: 0,
+			logical: 0,
+		}
+	}
+
+	/// Sets the wall-clock time (in milliseconds since the Unix epoch)
+	/// and the logical counter of the (newly constructed) hybrid logical clock.
+	///
+	/// ```ignore
+	/// let clock = HybridClock::new("some_id").set_time(1570000000000,2);
+	/// ```
+	pub fn set_time (mut self, wall: u64, logical: u64) -> HybridClock {
+		self.wall = wall;
+		self.logical = logical;
+		self
+	}
+
+	/// Returns the current wall-clock time in milliseconds since the Unix epoch.
+	pub fn now () -> u64 {
+		SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis() as u64
+	}
+
+	/// Returns the identifier of the hybrid logical clock.
+	pub fn id (&self) -> &str {
+		&self.id
+	}
+
+	/// Returns the wall-clock time of the hybrid logical clock in milliseconds since the Unix epoch.
+	pub fn wall (&self) -> u64 {
+		self.wall
+	}
+
+	/// Returns the logical counter of the hybrid logical clock.
+	pub fn logical (&self) -> u64 {
+		self.logical
+	}
+
+	/// Advances the hybrid logical clock to the current wall-clock time,
+	/// or advances the logical counter if the wall clock has not passed the time of the hybrid logical clock.
+	pub fn tick (&mut self) {
+		let now = HybridClock::now();
+		if now > self.wall {
+			self.wall = now;
+			self.logical = 0;
+		}
+		else {
+			self.logical += 1;
+		}
+	}
+
+	/// Merges `o` to `self` in the following manner:
+	/// * if `o` is ahead of the local wall clock by more than [`MAX_DRIFT`], do nothing
+	/// * if `o` is ahead of `self` in wall-clock time and logical counter,
+	/// set the wall-clock time and logical counter of `self` to those of `o`,
+	/// otherwise do nothing
+	/// * `o` is never modified
+	///
+	/// [`MAX_DRIFT`]: #associatedconstant.MAX_DRIFT
+	pub fn merge (&mut self, o: &HybridClock) {
+		if o.wall > HybridClock::now() + HybridClock::MAX_DRIFT {
+			return;
+		}
+		if (self.wall,self.logical) < (o.wall,o.logical) {
+			self.wall = o.wall;
+			self.logical = o.logical;
+		}
+	}
+}
+
+impl Clock for HybridClock {
+	fn new (id: &str) -> Self {
+		HybridClock::new(id)
+	}
+
+	fn id (&self) -> &str {
+		self.id()
+	}
+
+	/// Returns the wall-clock time of the hybrid logical clock.
+	fn time (&self) -> u64 {
+		self.wall
+	}
+
+	fn tick (&mut self) {
+		self.tick()
+	}
+
+	fn merge (&mut self, o: &Self) {
+		self.merge(o)
+	}
+
+	fn happened_before (&self, o: &Self) -> Option<bool> {
+		if (self.wall,self.logical) >= (o.wall,o.logical) {
+			Some(false)
+		}
+		else {
+			None
+		}
+	}
+
+	/// Rejects clocks that are ahead of the local wall clock by more than [`MAX_DRIFT`].
+	///
+	/// Whether a clock is accepted depends on the time it is checked at, so a log does not
+	/// exclude rejected entries for good: they are joined once the local wall clock catches up.
+	///
+	/// [`MAX_DRIFT`]: ../hybrid_clock/struct.HybridClock.html#associatedconstant.MAX_DRIFT
+	fn accepts (&self, o: &Self) -> bool {
+		o.wall <= HybridClock::now() + HybridClock::MAX_DRIFT
+	}
+}
+
+impl PartialEq for HybridClock {
+	fn eq (&self, other: &Self) -> bool {
+		self.wall == other.wall && self.logical == other.logical && self.id == other.id
+	}
+}
+
+impl Eq for HybridClock {}
+
+impl Ord for HybridClock {
+	fn cmp (&self, other: &Self) -> Ordering {
+		(self.wall,self.logical,&self.id).cmp(&(other.wall,other.logical,&other.id))
+	}
+}
+
+impl PartialOrd for HybridClock {
+	fn partial_cmp (&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
@@ -6,9 +6,10 @@ use sha2::{Sha256,Digest};
 use secp256k1::{Secp256k1,Message,All,Signature,PublicKey,SecretKey};
 use rand::rngs::OsRng;
 use hex;
+use serde::{Serialize,Deserialize};
 
 /// A struct holding identifier and public key signatures for an identity.
-#[derive(Eq,PartialEq,Clone)]
+#[derive(Eq,PartialEq,Clone,Debug,Serialize,Deserialize)]
 pub struct Signatures {
 	id: String,
 	pub_key: String,
@@ -42,7 +43,7 @@ impl Signatures {
 }
 
 /// An identity to determine ownership of the data stored in the log.
-#[derive(Eq,PartialEq,Clone)]
+#[derive(Eq,PartialEq,Clone,Debug,Serialize,Deserialize)]
 pub struct Identity {
 	id: String,
 	pub_key: String,
@@ -130,6 +131,20 @@ pub trait Identificator {
 	/// Currently **does not store the created identity** anywhere.
 	fn create (&mut self, id: &str) -> Identity;
 
+	/// Create a new identity from a cleartext identifier, using the hex-encoded secret key `sk`
+	/// as the identity key. Store the keys associated with the created identity in the identificator.
+	///
+	/// Creating an identity from the same identifier and secret key always produces the same identity.
+	///
+	/// Returns `None` if `sk` is not a valid secret key.
+	fn create_from_secret_key (&mut self, id: &str, sk: &str) -> Option<Identity>;
+
+	/// Create a new identity from a cleartext identifier, deriving the identity key from the seed `seed`.
+	/// Store the keys associated with the created identity in the identificator.
+	///
+	/// Creating an identity from the same identifier and seed always produces the same identity.
+	fn create_from_seed (&mut self, id: &str, seed: &[u8]) -> Identity;
+
 	/// Return the secret key&mdash;public key pair stored under the store key `key`.
 	fn get (&self, key: &str) -> Option<&Keys>;
 
@@ -142,6 +157,16 @@ pub trait Identificator {
 	///
 	/// Returns `true` if it was, otherwise returns `false`.
 	fn sign (&self, msg: &str, keys: &Keys) -> String;
+
+	/// Verify that the signatures of `identity` were produced by its keys.
+	///
+	/// Returns `true` if they were, otherwise returns `false`.
+	fn verify_identity (&self, identity: &Identity) -> bool {
+		let mut pub_key_id_sign = identity.pub_key().to_owned();
+		pub_key_id_sign.push_str(identity.signatures().id());
+		self.verify(identity.id(),identity.signatures().id(),identity.pub_key()) &&
+		self.verify(&pub_key_id_sign,identity.signatures().pub_key(),identity.id())
+	}
 }
 
 /// The default identity provider, or [*identificator*],
@@ -166,17 +191,29 @@ impl DefaultIdentificator {
 	fn put (&mut self, k: &str, v: Keys) {
 		self.keystore.insert(k.to_owned(),v);
 	}
-}
 
-impl Identificator for DefaultIdentificator {
-	fn create (&mut self, id: &str) -> Identity {
-		let mut rng = OsRng::new().unwrap();
+	//hashes the input until the digest is a valid secret key,
+	//which in practice is always the first digest
+	fn derive_key (input: &[u8]) -> SecretKey {
+		let mut hasher = Sha256::new();
+		hasher.input(input);
+		let mut dig = hasher.result();
+		loop {
+			if let Ok(sk) = SecretKey::from_slice(&dig) {
+				return sk;
+			}
+			let mut hasher = Sha256::new();
+			hasher.input(&dig);
+			dig = hasher.result();
+		}
+	}
 
-		let (secret_key,id_hash) = self.secp.generate_keypair(&mut rng);
+	fn create_from_keys (&mut self, id: &str, secret_key: SecretKey, middle_key: SecretKey) -> Identity {
+		let id_hash = PublicKey::from_secret_key(&self.secp,&secret_key);
 		let (sk,ih) = (&secret_key.to_string(),&id_hash.serialize_uncompressed().iter().map(|&x| format!("{:02x}",x)).collect::<String>());
 		self.put(id,Keys::new(sk,ih));
 
-		let (middle_key,public_key) = self.secp.generate_keypair(&mut rng);
+		let public_key = PublicKey::from_secret_key(&self.secp,&middle_key);
 		let (mk,pk) = (&middle_key.to_string(),&public_key.serialize_uncompressed().iter().map(|&x| format!("{:02x}",x)).collect::<String>());
 		self.put(ih,Keys::new(mk,pk));
 
@@ -193,6 +230,27 @@ impl Identificator for DefaultIdentificator {
 
 		Identity::new(ih,pk,Signatures::new(&id_sign.to_string(),&pub_sign.to_string()))
 	}
+}
+
+impl Identificator for DefaultIdentificator {
+	fn create (&mut self, id: &str) -> Identity {
+		let mut rng = OsRng::new().unwrap();
+		let secret_key = SecretKey::new(&mut rng);
+		let middle_key = SecretKey::new(&mut rng);
+		self.create_from_keys(id,secret_key,middle_key)
+	}
+
+	fn create_from_secret_key (&mut self, id: &str, sk: &str) -> Option<Identity> {
+		let secret_key = SecretKey::from_slice(&hex::decode(sk).ok()?).ok()?;
+		let middle_key = DefaultIdentificator::derive_key(&secret_key[..]);
+		Some(self.create_from_keys(id,secret_key,middle_key))
+	}
+
+	fn create_from_seed (&mut self, id: &str, seed: &[u8]) -> Identity {
+		let secret_key = DefaultIdentificator::derive_key(seed);
+		let middle_key = DefaultIdentificator::derive_key(&secret_key[..]);
+		self.create_from_keys(id,secret_key,middle_key)
+	}
 
 	fn get (&self, key: &str) -> Option<&Keys> {
 		self.keystore.get(key)
@@ -202,9 +260,12 @@ impl Identificator for DefaultIdentificator {
 		let mut hasher = Sha256::new();
 		hasher.input(msg.as_bytes());
 		let dig = hasher.result();
-		match self.secp.verify(&Message::from_slice(&dig).unwrap(),
-		&Signature::from_str(sig).unwrap(),
-		&PublicKey::from_slice(&hex::decode(pk).unwrap()).unwrap()) {
+		//signatures and keys may come from untrusted sources
+		let (sig,pk) = match (Signature::from_str(sig),hex::decode(pk).map(|x| PublicKey::from_slice(&x))) {
+			(Ok(s),Ok(Ok(p)))	=>	(s,p),
+			_					=>	return false,
+		};
+		match self.secp.verify(&Message::from_slice(&dig).unwrap(),&sig,&pk) {
 			Ok(_)	=>	true,
 			_		=>	false,
 		}
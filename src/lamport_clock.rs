@@ -1,6 +1,8 @@
 use serde::{Serialize,Deserialize};
 use std::cmp::Ordering;
 
+use crate::clock::Clock;
+
 /// A [Lamport clock] for partial chronological ordering of unconnected events.
 ///
 /// [Lamport clock]: https://en.wikipedia.org/wiki/Lamport_clock
@@ -55,6 +57,39 @@ impl LamportClock {
 	}
 }
 
+impl Clock for LamportClock {
+	fn new (id: &str) -> Self {
+		LamportClock::new(id)
+	}
+
+	fn id (&self) -> &str {
+		self.id()
+	}
+
+	fn time (&self) -> u64 {
+		self.time()
+	}
+
+	fn tick (&mut self) {
+		self.tick()
+	}
+
+	fn merge (&mut self, o: &Self) {
+		self.merge(o)
+	}
+
+	/// A Lamport clock can only tell that an event did not happen before another
+	/// if its time is not less than that of the other.
+	fn happened_before (&self, o: &Self) -> Option<bool> {
+		if self.time >= o.time {
+			Some(false)
+		}
+		else {
+			None
+		}
+	}
+}
+
 impl PartialEq for LamportClock {
 	fn eq (&self, other: &Self) -> bool {
 		self.time == other.time && self.id == other.id
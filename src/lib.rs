@@ -2,17 +2,38 @@
 
 pub mod log;
 pub mod identity;
+pub mod clock;
 pub mod lamport_clock;
+pub mod vector_clock;
+pub mod hybrid_clock;
 pub mod entry;
+pub mod control;
+pub mod manifest;
+pub mod crypto;
+pub mod sort;
+pub mod validation;
+pub mod store;
+pub mod cache;
+pub mod car;
+pub mod event;
+pub mod reconcile;
+pub mod transport;
+pub mod replicator;
 
 #[cfg(test)]
 mod tests {
 	use std::rc::Rc;
+	use std::cell::RefCell;
+	use std::io::{Read,Write};
 
+	use tokio::runtime::Runtime;
 	use ipfs_api::IpfsClient;
 	use serde_json::json;
 
+	use super::clock::Clock;
 	use super::lamport_clock::LamportClock;
+	use super::vector_clock::VectorClock;
+	use super::hybrid_clock::HybridClock;
 	use super::identity::Identity;
 	use super::identity::Signatures;
 	use super::identity::DefaultIdentificator;
@@ -21,6 +42,18 @@ mod tests {
 	use super::log::LogOptions;
 	use super::entry::Entry;
 	use super::entry::EntryOrHash;
+	use super::control::{Control,KeyRotation,Revocation,KeyEpoch,KeyRegistry,Checkpoint};
+	use super::manifest::SignedManifest;
+	use super::crypto::LogKey;
+	use super::sort::SortStrategy;
+	use super::validation::{self,Problem};
+	use super::store::{Store,MemoryStore,PinningPolicy};
+	use super::cache::FileCache;
+	use super::car;
+	use super::event::Event;
+	use super::reconcile::{Reconciler,Message,BloomFilter};
+	use super::transport::{Transport,MemoryNetwork,TcpTransport,MAX_FRAME,MAX_CONNECTIONS};
+	use super::replicator::Replicator;
 
 	fn ipfs () -> Rc<IpfsClient> {
 		Rc::new(IpfsClient::default())
@@ -140,7 +173,8 @@ mod tests {
 	fn serialize () {
 		let expected = json!({
 			"id": "AAA",
-			"heads": ["QmREuiyqTuJrcWr5BLrT9d9p8dcvdWvwc4JJMHpKcei4Em"],
+			"heads": ["QmfGVwSma2BgivoAj1WkjMZBEvpCD17XiDG9XwkL7xx9mT"],
+			"sort": "last-write-wins",
 		}).to_string();
 		let ipfs = ipfs();
 		let mut log = Log::new(ipfs.clone(),identity1(),LogOptions::new().id("AAA"));
@@ -151,9 +185,9 @@ mod tests {
 		//...
 
 		//extra
-		let log2 = Log::from_multihash(ipfs.clone(),identity1(),LogOptions::new().id("AAA"),"QmREuiyqTuJrcWr5BLrT9d9p8dcvdWvwc4JJMHpKcei4Em");
+		let log2 = Log::from_multihash(ipfs.clone(),identity1(),LogOptions::new().id("AAA"),"QmfGVwSma2BgivoAj1WkjMZBEvpCD17XiDG9XwkL7xx9mT");
 		assert_eq!(log.snapshot(),log2.snapshot());
-		let log3 = Log::from_multihash(ipfs,identity1(),LogOptions::new().id("AAA"),"QmQyM8vsbzs6ibi6DFRhXVFurR1AaFyJkPnnvQTeNEdbZu");
+		let log3 = Log::from_multihash(ipfs,identity1(),LogOptions::new().id("AAA"),"QmdCuMPVgcZFCiFhoLyhBmicVeKSNRa5zBCADx2DxUWJe6");
 		assert_ne!(log.snapshot(),log3.snapshot());
 	}
 
@@ -287,4 +321,1176 @@ mod tests {
 		let pub_key_id_sign_sign = idpr.sign(&pub_key_id_sign,key);
 		assert_eq!(id.signatures().pub_key(),pub_key_id_sign_sign);
 	}
+
+	#[test]
+	fn deterministic_identities () {
+		let mut idpr1 = DefaultIdentificator::new();
+		let mut idpr2 = DefaultIdentificator::new();
+		let id1 = idpr1.create_from_seed("local_id",b"some seed");
+		let id2 = idpr2.create_from_seed("local_id",b"some seed");
+		assert!(id1 == id2);
+		assert!(id1 != idpr1.create_from_seed("another_id",b"another seed"));
+
+		let sk = idpr1.get("local_id").unwrap().sec_key().to_owned();
+		let id3 = idpr2.create_from_secret_key("other_id",&sk).unwrap();
+		assert!(id1 == id3);
+		assert!(idpr2.create_from_secret_key("other_id","not a key").is_none());
+
+		let signer = idpr2.get(id3.id()).unwrap();
+		assert!(idpr2.verify(id3.id(),id3.signatures().id(),signer.pub_key()));
+	}
+
+	#[test]
+	fn key_rotation () {
+		let mut idpr = DefaultIdentificator::new();
+		let a = idpr.create_from_seed("a",b"a");
+		let b = idpr.create_from_seed("b",b"b");
+		let c = idpr.create_from_seed("c",b"c");
+
+		let rotation = KeyRotation::new(&idpr,&a,&b).unwrap();
+		assert!(rotation.verify(&idpr));
+		let revocation = Revocation::new(&idpr,&b,a.pub_key(),3).unwrap();
+		let forged = Revocation::new(&idpr,&c,b.pub_key(),3).unwrap();
+		let es: Vec<Entry> = vec![
+			Entry::new(a.clone(),"A",&Control::Revocation(revocation).to_payload(),&[],None),
+			Entry::new(a.clone(),"A",&Control::KeyRotation(rotation).to_payload(),&[],None),
+			Entry::new(c.clone(),"A",&Control::Revocation(forged).to_payload(),&[],None),
+			Entry::new(c.clone(),"A","not a control record",&[],None),
+		];
+
+		let mut keys = KeyRegistry::new();
+		keys.register(&es);
+		assert!(keys.is_successor(a.pub_key(),b.pub_key()));
+		assert_eq!(keys.current(a.pub_key()),b.pub_key());
+		assert!(!keys.is_revoked(a.pub_key(),3));
+		assert!(keys.is_revoked(a.pub_key(),4));
+		assert!(!keys.is_revoked(b.pub_key(),4));
+
+		//conflicting rotations resolve in the same way whatever order they are registered in
+		let to_b: Entry = Entry::new(a.clone(),"A",&Control::KeyRotation(KeyRotation::new(&idpr,&a,&b).unwrap()).to_payload(),&[],None);
+		let to_c: Entry = Entry::new(a.clone(),"A",&Control::KeyRotation(KeyRotation::new(&idpr,&a,&c).unwrap()).to_payload(),&[],None);
+		let (mut keys1,mut keys2) = (KeyRegistry::new(),KeyRegistry::new());
+		keys1.register(vec![&to_b,&to_c]);
+		keys2.register(vec![&to_c,&to_b]);
+		assert_eq!(keys1.current(a.pub_key()),keys2.current(a.pub_key()));
+	}
+
+	#[test]
+	fn revocation_on_join () {
+		let mut idpr = DefaultIdentificator::new();
+		let a = idpr.create_from_seed("a",b"a");
+		let b = idpr.create_from_seed("b",b"b");
+		let store = Rc::new(MemoryStore::new());
+		let mut x = Log::new(store.clone(),b.clone(),LogOptions::new().id("A"));
+		x.append("one",None);
+		let one = x.values();
+		let mut y = Log::new(store.clone(),a.clone(),LogOptions::new().id("A"));
+		y.join(&x,None);
+		let two = y.append("two",None).hash().to_owned();
+		let three = y.append("three",None).hash().to_owned();
+		x.join(&y,None);
+
+		//a revokes its key after "two", "three" is removed and "two" becomes a head again
+		let mut z = Log::new(store.clone(),a.clone(),LogOptions::new().id("A").entries(&one));
+		let revocation = z.revoke(&idpr,a.pub_key(),2).unwrap().hash().to_owned();
+		let removed = Rc::new(RefCell::new(Vec::new()));
+		let sink = removed.clone();
+		x.subscribe(move |e| if let Event::Truncated {removed} = e {
+			sink.borrow_mut().extend(removed.to_owned());
+		});
+		x.join(&z,None);
+		assert_eq!(*removed.borrow(),vec![three.to_owned()]);
+		assert!(!x.has(&three));
+		assert!(x.is_excluded(&three));
+		assert!(x.is_complete());
+		let mut heads = x.heads().iter().map(|e| e.hash().to_owned()).collect::<Vec<_>>();
+		heads.sort();
+		let mut expected = vec![two,revocation];
+		expected.sort();
+		assert_eq!(heads,expected);
+		assert_eq!(x.append("four",None).next().len(),2);
+		assert!(y.join(&x,None).is_some());
+		assert!(!y.has(&three));
+
+		//entries of revoked keys left out of a join are excluded as when loading
+		let mut w = Log::new(store.clone(),b.clone(),LogOptions::new().id("A").entries(&one));
+		w.join(&z,None);
+		let mut v = Log::new(store.clone(),a.clone(),LogOptions::new().id("A").entries(&one));
+		v.append("two",None);
+		v.append("three",None);
+		w.join(&v,None);
+		assert!(!w.has(&three));
+		assert!(w.is_excluded(&three));
+		assert!(w.is_complete());
+	}
+
+	#[test]
+	#[should_panic(expected = "is not allowed to write")]
+	fn revoked_append_after_join () {
+		let mut idpr = DefaultIdentificator::new();
+		let a = idpr.create_from_seed("a",b"a");
+		let b = idpr.create_from_seed("b",b"b");
+		let store = Rc::new(MemoryStore::new());
+		let mut x = Log::new(store.clone(),a.clone(),LogOptions::new().id("A"));
+		x.append("one",None);
+		let time = x.clock().time() + 1;
+		x.revoke(&idpr,a.pub_key(),time).unwrap();
+		let mut y = Log::new(store.clone(),b.clone(),LogOptions::new().id("A"));
+		y.append("two",None);
+		x.join(&y,None);
+		x.append("three",None);
+	}
+
+	#[test]
+	fn signed_manifest () {
+		let mut idpr = DefaultIdentificator::new();
+		let id = idpr.create_from_seed("local_id",b"seed");
+		let clock = LamportClock::new(id.pub_key()).set_time(3);
+		let heads = vec!["QmA".to_owned(),"QmB".to_owned()];
+		let manifest = SignedManifest::new(&idpr,&id,"AAA",&heads,Some("last-write-wins"),&clock).unwrap();
+		assert!(manifest.verify(&idpr,id.pub_key()));
+
+		let parsed: SignedManifest = SignedManifest::from_json(&manifest.json()).unwrap();
+		assert!(parsed.verify(&idpr,id.pub_key()));
+		assert_eq!(parsed.heads(),&heads[..]);
+		assert_eq!(parsed.sort(),Some("last-write-wins"));
+
+		let tampered = manifest.json().replace("QmB","QmC");
+		assert!(!SignedManifest::<LamportClock>::from_json(&tampered).unwrap().verify(&idpr,id.pub_key()));
+		let tampered = manifest.json().replace("last-write-wins","entry-hash");
+		assert!(!SignedManifest::<LamportClock>::from_json(&tampered).unwrap().verify(&idpr,id.pub_key()));
+		assert!(SignedManifest::<LamportClock>::from_json("{}").is_none());
+
+		//a manifest signed by anyone but the expected publisher is rejected
+		let other = idpr.create_from_seed("other_id",b"other");
+		let forged = SignedManifest::new(&idpr,&other,"AAA",&heads,Some("last-write-wins"),&clock).unwrap();
+		assert!(forged.verify(&idpr,other.pub_key()));
+		assert!(!forged.verify(&idpr,id.pub_key()));
+
+		let store = Rc::new(MemoryStore::new());
+		let mut log = Log::new(store.clone(),id.clone(),LogOptions::new().id("A").sort(SortStrategy::EntryHash));
+		log.append("one",None);
+		let manifest = log.manifest(&idpr).unwrap();
+		let restored = Log::from_signed_manifest(store.clone(),id.clone(),LogOptions::new(),&manifest,&idpr,id.pub_key()).unwrap();
+		assert_eq!(restored.len(),1);
+		assert_eq!(restored.sort_strategy().name(),"entry-hash");
+		assert!(Log::from_signed_manifest(store.clone(),id.clone(),LogOptions::new(),&manifest,&idpr,other.pub_key()).is_none());
+	}
+
+	#[test]
+	fn log_key () {
+		let key = LogKey::generate();
+		let sealed = key.seal("secret","AAA");
+		assert!(!sealed.contains("secret"));
+		assert_eq!(key.open(&sealed,"AAA").unwrap(),"secret");
+		assert_eq!(key.open(&sealed,"BBB"),None);
+		assert_eq!(LogKey::generate().open(&sealed,"AAA"),None);
+		assert_eq!(key.open("secret","AAA"),None);
+		assert!(LogKey::from_hex(&key.to_hex()).unwrap() == key);
+	}
+
+	#[test]
+	fn encrypted_values () {
+		let ipfs = ipfs();
+		let key = LogKey::generate();
+		let mut log = Log::new(ipfs.clone(),identity1(),LogOptions::new().id("A").key(key.clone()));
+		log.append("hello1",None);
+		log.append("hello2",None);
+		assert_ne!(log.values()[0].payload(),"hello1");
+		assert_eq!(log.payload(&log.values()[0]).unwrap(),"hello1");
+		assert_eq!(log.payload(&log.values()[1]).unwrap(),"hello2");
+		assert_eq!(log.to_string(),"hello2\n└─hello1\n");
+
+		let es = log.values();
+		let other = Log::new(ipfs.clone(),identity2(),LogOptions::new().id("A").entries(&es));
+		assert_eq!(other.len(),2);
+		assert_eq!(other.payload(&other.values()[0]),None);
+
+		//a sealed payload replayed in another entry does not open
+		let sealed = es[0].payload().to_owned();
+		let replayed = Rc::new(Entry::new(identity1(),"A",&sealed,&[],Some(LamportClock::new("x").set_time(7))));
+		let log2 = Log::new(ipfs.clone(),identity1(),LogOptions::new().id("A").key(key).entries(&[replayed]));
+		assert_eq!(log2.payload(&log2.values()[0]),None);
+
+		//plaintext looking like a sealed payload is not taken for one
+		let mut plain = Log::new(ipfs,identity1(),LogOptions::new().id("A"));
+		plain.append(&sealed,None);
+		let e = plain.values()[0].clone();
+		assert_ne!(e.payload(),sealed);
+		assert_eq!(plain.payload(&e).unwrap(),sealed);
+
+		//nor is plaintext looking like a control record, such as a checkpoint
+		let forged = Control::Checkpoint(Checkpoint::new("forged",Vec::new())).to_payload();
+		let e = plain.append(&forged,None).clone();
+		assert_eq!(Control::from_entry(&e),None);
+		assert_eq!(plain.payload(&e).unwrap(),forged);
+		assert!(plain.latest_checkpoint().is_none());
+		assert_eq!(plain.prune(),0);
+		assert_eq!(plain.len(),2);
+	}
+
+	#[test]
+	fn key_epochs () {
+		let mut idpr = DefaultIdentificator::new();
+		let a = idpr.create_from_seed("a",b"a");
+		let b = idpr.create_from_seed("b",b"b");
+		let key = LogKey::generate().set_epoch(2);
+		let record = KeyEpoch::new(&key,&[a.pub_key()]).unwrap();
+		assert_eq!(record.epoch(),2);
+		assert_eq!(record.readers(),vec![a.pub_key()]);
+		assert!(record.unwrap_key(idpr.get(a.id()).unwrap()).unwrap() == key);
+		assert!(record.unwrap_key(idpr.get(b.id()).unwrap()).is_none());
+		assert!(KeyEpoch::new(&key,&["not a key"]).is_none());
+	}
+
+	#[test]
+	fn remove_readers () {
+		let ipfs = ipfs();
+		let mut idpr = DefaultIdentificator::new();
+		let a = idpr.create_from_seed("a",b"a");
+		let b = idpr.create_from_seed("b",b"b");
+		let mut log = Log::new(ipfs.clone(),a.clone(),LogOptions::new().id("A").key(LogKey::generate()));
+		log.add_readers(&[a.pub_key(),b.pub_key()]).unwrap();
+		log.append("before",None);
+		log.remove_readers(&[b.pub_key()]).unwrap();
+		log.append("after",None);
+		assert_eq!(log.readers(),vec![a.pub_key().to_owned()]);
+
+		let es = log.values();
+		let mut other = Log::new(ipfs.clone(),b.clone(),LogOptions::new().id("A").entries(&es));
+		assert_eq!(other.unlock(&idpr),1);
+		let payloads = other.values().iter().filter_map(|x| other.payload(x)).collect::<Vec<_>>();
+		assert!(payloads.contains(&"before".to_owned()));
+		assert!(!payloads.contains(&"after".to_owned()));
+		assert_eq!(log.payload(&log.values()[3]).unwrap(),"after");
+
+		//readers removed concurrently on two replicas start two keys of the same epoch
+		let c = idpr.create_from_seed("c",b"c");
+		let mut x = Log::new(ipfs.clone(),a.clone(),LogOptions::new().id("B").key(LogKey::generate()));
+		x.add_readers(&[a.pub_key(),b.pub_key(),c.pub_key()]).unwrap();
+		let mut y = Log::new(ipfs,b.clone(),LogOptions::new().id("B").entries(&x.values()));
+		assert_eq!(y.unlock(&idpr),1);
+		x.remove_readers(&[c.pub_key()]).unwrap();
+		y.remove_readers(&[c.pub_key()]).unwrap();
+		x.append("from a",None);
+		y.append("from b",None);
+		x.join(&y,None);
+		y.join(&x,None);
+		assert_eq!(x.unlock(&idpr),1);
+		assert_eq!(y.unlock(&idpr),1);
+		for l in &[&x,&y] {
+			let payloads = l.values().iter().filter_map(|e| l.payload(e)).collect::<Vec<_>>();
+			assert!(payloads.contains(&"from a".to_owned()));
+			assert!(payloads.contains(&"from b".to_owned()));
+		}
+		x.append("after a",None);
+		y.join(&x,None);
+		assert_eq!(y.payload(y.values().last().unwrap()).unwrap(),"after a");
+	}
+
+	#[test]
+	fn vector_clock () {
+		let mut x = VectorClock::new("x");
+		x.tick();
+		let mut y = VectorClock::new("y");
+		y.merge(&x);
+		y.tick();
+		let mut z = x.clone();
+		z.tick();
+		assert_eq!(Clock::happened_before(&x,&y),Some(true));
+		assert_eq!(Clock::happened_before(&y,&x),Some(false));
+		assert_eq!(Clock::happened_before(&y,&z),Some(false));
+		assert_eq!(Clock::happened_before(&z,&y),Some(false));
+		assert!(x < y && x < z);
+		assert_eq!(y.time(),2);
+		y.merge(&z);
+		assert_eq!(y.get("x"),2);
+		assert_eq!(y.get("y"),1);
+	}
+
+	#[test]
+	fn causality () {
+		let id = identity1();
+		let e1 = Entry::new(id.clone(),"A","one",&[],Some(LamportClock::new("x").set_time(1)));
+		let e2 = Entry::new(id.clone(),"A","two",&[EntryOrHash::Entry(&e1)],Some(LamportClock::new("x").set_time(2)));
+		let e3 = Entry::new(id.clone(),"A","three",&[EntryOrHash::Entry(&e1)],Some(LamportClock::new("y").set_time(2)));
+		let e4 = Entry::new(id.clone(),"A","four",&[EntryOrHash::Entry(&e3)],Some(LamportClock::new("y").set_time(3)));
+		let es = &[Rc::new(e1),Rc::new(e2),Rc::new(e3),Rc::new(e4)];
+		let log = Log::new(ipfs(),id.clone(),LogOptions::new().id("A").entries(es));
+		assert!(log.happened_before("one","two"));
+		assert!(log.happened_before("one","four"));
+		assert!(!log.happened_before("two","one"));
+		assert!(!log.happened_before("two","four"));
+		assert!(log.is_concurrent("two","four"));
+		assert!(!log.is_concurrent("one","four"));
+		assert!(!log.is_concurrent("one","one"));
+
+		let mut c1 = VectorClock::new("x");
+		c1.tick();
+		let mut c2 = c1.clone();
+		c2.tick();
+		let mut c3 = VectorClock::new("y");
+		c3.merge(&c1);
+		c3.tick();
+		let e1 = Entry::new(id.clone(),"A","one",&[],Some(c1));
+		let e2 = Entry::new(id.clone(),"A","two",&[EntryOrHash::Entry(&e1)],Some(c2));
+		let e3 = Entry::new(id.clone(),"A","three",&[EntryOrHash::Entry(&e1)],Some(c3));
+		let es = &[Rc::new(e1),Rc::new(e2),Rc::new(e3)];
+		let log = Log::new(ipfs(),id,LogOptions::default().id("A").entries(es));
+		assert!(log.happened_before("one","three"));
+		assert!(log.is_concurrent("two","three"));
+		assert_eq!(log.clock().get("x"),2);
+
+		//writers keep their own entries in the vector clocks across joins
+		let mut idp = DefaultIdentificator::new();
+		let a = idp.create_from_seed("a",b"a");
+		let b = idp.create_from_seed("b",b"b");
+		let mut x: Log<VectorClock> = Log::new(ipfs(),a.clone(),LogOptions::default().id("B"));
+		let mut y: Log<VectorClock> = Log::new(ipfs(),b.clone(),LogOptions::default().id("B"));
+		let one = x.append("one",None).hash().to_owned();
+		y.join(&x,None);
+		let two = y.append("two",None).hash().to_owned();
+		let three = x.append("three",None).hash().to_owned();
+		x.join(&y,None);
+		let four = x.append("four",None).hash().to_owned();
+		assert!(x.happened_before(&one,&two));
+		assert!(x.is_concurrent(&two,&three));
+		assert!(x.happened_before(&two,&four));
+		assert!(x.happened_before(&three,&four));
+		assert_eq!(x.clock().get(a.pub_key()),3);
+		assert_eq!(x.clock().get(b.pub_key()),1);
+		assert_eq!(x.get(&two).unwrap().clock().id(),b.pub_key());
+	}
+
+	#[test]
+	fn hybrid_clock () {
+		let mut x = HybridClock::new("x");
+		x.tick();
+		assert!(x.wall() > 0);
+		let ahead = HybridClock::new("y").set_time(x.wall() + 10_000,5);
+		x.merge(&ahead);
+		x.tick();
+		assert!(x > ahead);
+		assert_eq!(x.wall(),ahead.wall());
+		assert_eq!(x.logical(),6);
+		assert_eq!(Clock::happened_before(&x,&ahead),Some(false));
+		assert!(x.accepts(&ahead));
+		let far = HybridClock::new("z").set_time(HybridClock::now() + 2 * HybridClock::MAX_DRIFT,0);
+		assert!(!x.accepts(&far));
+		let before = x.clone();
+		x.merge(&far);
+		assert_eq!(x,before);
+	}
+
+	#[test]
+	fn hybrid_clock_drift () {
+		let id = identity1();
+		let now = HybridClock::now();
+		let e1 = Entry::new(id.clone(),"A","one",&[],Some(HybridClock::new("x").set_time(now,0)));
+		let e2 = Entry::new(id.clone(),"A","two",&[EntryOrHash::Entry(&e1)],Some(HybridClock::new("x").set_time(now,1)));
+		let e3 = Entry::new(id.clone(),"A","three",&[],Some(HybridClock::new("y").set_time(now + 2 * HybridClock::MAX_DRIFT,0)));
+		let e4 = Entry::new(id.clone(),"A","four",&[EntryOrHash::Entry(&e3)],Some(HybridClock::new("x").set_time(now,2)));
+		let e1 = Rc::new(e1);
+		let mut x = Log::new(ipfs(),id.clone(),LogOptions::default().id("A").entries(&[e1.clone()]));
+		let y = Log::new(ipfs(),id.clone(),LogOptions::default().id("A").entries(&[e1,Rc::new(e2),Rc::new(e3),Rc::new(e4)]));
+		x.join(&y,None);
+		assert!(x.has("two"));
+		assert!(!x.has("three"));
+		//descendants of rejected entries are rejected too
+		assert!(!x.has("four"));
+		assert!(!x.is_excluded("three"));
+		assert_eq!(x.heads().len(),1);
+		assert_eq!(x.values().last().unwrap().payload(),"two");
+
+		//the parents of a rejected head become heads
+		let e1 = Entry::new(id.clone(),"A","one",&[],Some(HybridClock::new("x").set_time(now,0)));
+		let e2 = Entry::new(id.clone(),"A","two",&[EntryOrHash::Entry(&e1)],Some(HybridClock::new("x").set_time(now,1)));
+		let e5 = Entry::new(id.clone(),"A","five",&[EntryOrHash::Entry(&e2)],Some(HybridClock::new("y").set_time(now + 2 * HybridClock::MAX_DRIFT,0)));
+		let e1 = Rc::new(e1);
+		let y = Log::new(ipfs(),id.clone(),LogOptions::default().id("A").entries(&[e1.clone(),Rc::new(e2),Rc::new(e5)]));
+		let mut x = Log::new(ipfs(),id,LogOptions::default().id("A").entries(&[e1]));
+		assert_eq!(y.heads().iter().map(|x| x.payload()).collect::<Vec<_>>(),vec!["five"]);
+		x.join(&y,None);
+		assert!(!x.has("five"));
+		assert_eq!(x.heads().iter().map(|x| x.payload()).collect::<Vec<_>>(),vec!["two"]);
+		assert_eq!(x.values().iter().map(|x| x.payload()).collect::<Vec<_>>(),vec!["one","two"]);
+	}
+
+	#[test]
+	fn ancestors () {
+		let id = identity1();
+		let e1 = Entry::new(id.clone(),"A","one",&[],Some(LamportClock::new("x").set_time(1)));
+		let e2 = Entry::new(id.clone(),"A","two",&[EntryOrHash::Entry(&e1)],Some(LamportClock::new("x").set_time(2)));
+		let e3 = Entry::new(id.clone(),"A","three",&[EntryOrHash::Entry(&e1)],Some(LamportClock::new("y").set_time(2)));
+		let e4 = Entry::new(id.clone(),"A","four",&[EntryOrHash::Entry(&e2),EntryOrHash::Entry(&e3)],Some(LamportClock::new("x").set_time(3)));
+		let e5 = Entry::new(id.clone(),"A","five",&[EntryOrHash::Entry(&e2)],Some(LamportClock::new("z").set_time(3)));
+		let es = &[Rc::new(e1),Rc::new(e2),Rc::new(e3),Rc::new(e4),Rc::new(e5)];
+		let log = Log::new(ipfs(),id,LogOptions::new().id("A").entries(es));
+		let payloads = |es: Vec<Rc<Entry>>| es.iter().map(|x| x.payload().to_owned()).collect::<Vec<_>>();
+
+		assert!(log.is_ancestor("one","four"));
+		assert!(log.is_ancestor("two","four"));
+		assert!(!log.is_ancestor("three","five"));
+		assert!(!log.is_ancestor("four","one"));
+		assert!(!log.is_ancestor("four","four"));
+		assert_eq!(payloads(log.causal_past("four")),vec!["one","two","three"]);
+		assert_eq!(payloads(log.causal_future("two")),vec!["four","five"]);
+		assert_eq!(payloads(log.causal_future("three")),vec!["four"]);
+		assert_eq!(payloads(log.common_ancestors("four","five")),vec!["one","two"]);
+		assert_eq!(log.lowest_common_ancestor("four","five").unwrap().payload(),"two");
+		assert_eq!(log.lowest_common_ancestor("three","five").unwrap().payload(),"one");
+		assert_eq!(log.lowest_common_ancestor("two","four").unwrap().payload(),"two");
+		assert_eq!(log.lowest_common_ancestor("two","zero"),None);
+	}
+
+	#[test]
+	fn children () {
+		let id = identity1();
+		let e1 = Entry::new(id.clone(),"A","one",&[],Some(LamportClock::new("x").set_time(1)));
+		let e2 = Entry::new(id.clone(),"A","two",&[EntryOrHash::Entry(&e1)],Some(LamportClock::new("x").set_time(2)));
+		let e3 = Entry::new(id.clone(),"A","three",&[EntryOrHash::Entry(&e1)],Some(LamportClock::new("y").set_time(2)));
+		let e4 = Entry::new(id.clone(),"A","four",&[EntryOrHash::Entry(&e2),EntryOrHash::Entry(&e3)],Some(LamportClock::new("x").set_time(3)));
+		let (e1,e2,e3,e4) = (Rc::new(e1),Rc::new(e2),Rc::new(e3),Rc::new(e4));
+		let mut log1 = Log::new(ipfs(),id.clone(),LogOptions::new().id("A").entries(&[e1.clone(),e2.clone()]));
+		let log2 = Log::new(ipfs(),id.clone(),LogOptions::new().id("A").entries(&[e1,e2,e3,e4]));
+		let payloads = |es: Vec<Rc<Entry>>| es.iter().map(|x| x.payload().to_owned()).collect::<Vec<_>>();
+
+		assert_eq!(payloads(log1.children("one")),vec!["two"]);
+		assert_eq!(payloads(log1.descendants("two")),Vec::<String>::new());
+		log1.join(&log2,None);
+		assert_eq!(payloads(log1.children("one")),vec!["two","three"]);
+		assert_eq!(payloads(log1.children("two")),vec!["four"]);
+		assert_eq!(payloads(log1.descendants("one")),vec!["two","three","four"]);
+		let five = log1.append("five",None).hash().to_owned();
+		assert_eq!(payloads(log1.children("four")),vec!["five"]);
+		assert_eq!(log1.descendants("one").last().unwrap().hash(),five);
+		assert_eq!(log1.children("zero"),Vec::new());
+	}
+
+	#[test]
+	fn sort_strategies () {
+		let id = identity1();
+		let e1 = Rc::new(Entry::new(id.clone(),"A","one",&[],Some(LamportClock::new("x").set_time(1))));
+		let e2 = Rc::new(Entry::new(id.clone(),"A","two",&[],Some(LamportClock::new("y").set_time(1))));
+		let es = &[e1,e2];
+		let by_payload = || SortStrategy::custom("by-payload",|a: &Entry,b: &Entry| b.payload().cmp(a.payload())).unwrap();
+
+		assert_eq!(SortStrategy::<LamportClock>::from_name("entry-hash"),Some(SortStrategy::EntryHash));
+		assert_eq!(SortStrategy::<LamportClock>::from_name("by-payload"),None);
+		assert!(SortStrategy::custom("last-write-wins",|a: &Entry,b: &Entry| a.cmp(b)).is_none());
+
+		let mut log1 = Log::new(ipfs(),id.clone(),LogOptions::new().id("A").entries(es));
+		let mut log2 = Log::new(ipfs(),id.clone(),LogOptions::new().id("A").entries(es).sort(by_payload()));
+		let log3 = Log::new(ipfs(),id.clone(),LogOptions::new().id("A").entries(es).sort(by_payload()));
+		assert_eq!(log1.sort_strategy().name(),"last-write-wins");
+		assert_eq!(log2.values()[0].payload(),"two");
+		assert!(log2.json().contains("\"sort\":\"by-payload\""));
+		assert!(log1.join(&log2,None).is_none());
+		assert!(log2.join(&log3,None).is_some());
+
+		//unnamed strategies cannot be told apart, so their logs are not joined
+		assert!(SortStrategy::custom("",|a: &Entry,b: &Entry| a.cmp(b)).is_none());
+		let unnamed = || LogOptions::new().id("A").entries(es).fn_sort(|a: &Entry,b: &Entry| b.payload().cmp(a.payload()));
+		let mut log4 = Log::new(ipfs(),id.clone(),unnamed());
+		let log5 = Log::new(ipfs(),id.clone(),unnamed());
+		assert_eq!(log4.values()[0].payload(),"two");
+		assert!(log4.join(&log5,None).is_none());
+	}
+
+	#[test]
+	fn writer_priority () {
+		let id = identity1();
+		let e1 = Rc::new(Entry::new(id.clone(),"A","admin",&[],Some(LamportClock::new("admin").set_time(1))));
+		let e2 = Rc::new(Entry::new(id.clone(),"A","member",&[],Some(LamportClock::new("member").set_time(1))));
+		let e3 = Rc::new(Entry::new(id.clone(),"A","guest",&[],Some(LamportClock::new("guest").set_time(1))));
+		let e4 = Rc::new(Entry::new(id.clone(),"A","later",&[EntryOrHash::Entry(&e1)],Some(LamportClock::new("member").set_time(2))));
+		let es = &[e1,e2,e3,e4];
+		let strategy = SortStrategy::WriterPriority(vec!["admin".to_owned(),"member".to_owned()]);
+		let payloads = |es: Vec<Rc<Entry>>| es.iter().map(|x| x.payload().to_owned()).collect::<Vec<_>>();
+
+		let log = Log::new(ipfs(),id.clone(),LogOptions::new().id("A").entries(es));
+		assert_eq!(payloads(log.values()),vec!["admin","guest","member","later"]);
+		let log = Log::new(ipfs(),id.clone(),LogOptions::new().id("A").entries(es).sort(strategy.clone()));
+		assert_eq!(payloads(log.values()),vec!["guest","member","admin","later"]);
+		//an admin entry comes after concurrent member entries of later clock times, but a member entry still comes after its admin parent
+		let e5 = Rc::new(Entry::new(id.clone(),"A","admin late",&[],Some(LamportClock::new("admin").set_time(2))));
+		let e6 = Rc::new(Entry::new(id.clone(),"A","member edit",&[EntryOrHash::Entry(&es[3])],Some(LamportClock::new("member").set_time(5))));
+		let es = &[es[0].clone(),es[1].clone(),es[2].clone(),es[3].clone(),e5,e6];
+		let mut log = Log::new(ipfs(),id.clone(),LogOptions::new().id("A").entries(es).sort(strategy.clone()));
+		assert_eq!(payloads(log.values()),vec!["guest","member","admin","later","member edit","admin late"]);
+		assert_eq!(payloads(log.history(0).collect()),vec!["admin late","member edit","later","admin","member","guest"]);
+		assert_eq!(strategy.name(),"writer-priority:admin,member");
+		assert_eq!(SortStrategy::from_name("writer-priority:admin,member"),Some(strategy));
+	}
+
+	#[test]
+	fn topological_order () {
+		let id = identity1();
+		let e1 = Entry::new(id.clone(),"A","one",&[],Some(LamportClock::new("x").set_time(5)));
+		let e2 = Entry::new(id.clone(),"A","two",&[EntryOrHash::Entry(&e1)],Some(LamportClock::new("x").set_time(1)));
+		let e3 = Entry::new(id.clone(),"A","three",&[],Some(LamportClock::new("y").set_time(3)));
+		let e4 = Entry::new(id.clone(),"A","four",&[EntryOrHash::Entry(&e2),EntryOrHash::Entry(&e3)],Some(LamportClock::new("y").set_time(2)));
+		let es = &[Rc::new(e1),Rc::new(e2),Rc::new(e3),Rc::new(e4)];
+		let log = Log::new(ipfs(),id,LogOptions::new().id("A").entries(es));
+		let payloads = |es: Vec<Rc<Entry>>| es.iter().map(|x| x.payload().to_owned()).collect::<Vec<_>>();
+
+		assert_eq!(payloads(log.topological_order()),vec!["three","one","two","four"]);
+	}
+
+	#[test]
+	fn validation () {
+		let id = identity1();
+		let e1 = Entry::new(id.clone(),"A","one",&[],None);
+		let e2 = Entry::new(id.clone(),"A","two",&[EntryOrHash::Entry(&e1),EntryOrHash::Hash("zero".to_owned())],None);
+		let log1 = Log::new(ipfs(),id.clone(),LogOptions::new().id("A").entries(&[Rc::new(e1.clone()),Rc::new(e2.clone())]));
+		assert_eq!(log1.validate(),vec![Problem::MissingParent("two".to_owned(),"zero".to_owned())]);
+		assert_eq!(Log::find_tail_hashes(&log1.values()),vec!["zero"]);
+		assert_eq!(Entry::new(id.clone(),"A","three",&[EntryOrHash::Entry(&e2),EntryOrHash::Entry(&e2)],None).next(),&["two"]);
+
+		let x = Entry::new(id.clone(),"A","x",&[EntryOrHash::Hash("z".to_owned())],None);
+		let y = Entry::new(id.clone(),"A","y",&[EntryOrHash::Entry(&x)],None);
+		let z = Entry::new(id.clone(),"A","z",&[EntryOrHash::Entry(&y),EntryOrHash::Entry(&e1)],None);
+		let s = Entry::new(id.clone(),"A","s",&[EntryOrHash::Hash("s".to_owned())],None);
+		let b = Entry::new(id.clone(),"B","b",&[EntryOrHash::Entry(&e1)],None);
+		let es = &[Rc::new(e1),Rc::new(x),Rc::new(y),Rc::new(z),Rc::new(s),Rc::new(b)];
+		let problems = validation::validate("A",es);
+		assert_eq!(problems,vec![
+			Problem::SelfReference("s".to_owned()),
+			Problem::IdMismatch("b".to_owned()),
+			Problem::Cycle(vec!["x".to_owned(),"z".to_owned(),"y".to_owned()]),
+		]);
+		assert!(problems.iter().all(|x| x.is_fatal()));
+
+		//entries reachable from the heads of the joined log
+		let h = Entry::new(id.clone(),"A","h",&[EntryOrHash::Hash("x".to_owned())],None);
+		let t = Entry::new(id.clone(),"A","t",&[EntryOrHash::Hash("s".to_owned())],None);
+		let mut log1 = log1;
+		let log2 = Log::new(ipfs(),id.clone(),LogOptions::new().id("A").entries(&[es[1].clone(),es[2].clone(),es[3].clone(),Rc::new(h)]));
+		let log3 = Log::new(ipfs(),id.clone(),LogOptions::new().id("A").entries(&[es[4].clone(),Rc::new(t)]));
+		assert!(log1.join(&log2,None).is_none());
+		assert!(log1.join(&log3,None).is_none());
+		assert_eq!(log1.len(),2);
+
+		//entries of earlier versions list their only parent twice
+		let old: Entry = serde_json::from_value(json!({
+			"hash": "old",
+			"id": "A",
+			"payload": "old",
+			"next": ["two","two"],
+			"v": 1,
+			"clock": {"id": "x", "time": 3},
+		})).unwrap();
+		let log4 = Log::new(ipfs(),id.clone(),LogOptions::new().id("A").entries(&[Rc::new(old)]));
+		assert!(log1.join(&log4,None).is_some());
+		let problems = log1.validate();
+		assert_eq!(problems.len(),2);
+		assert!(problems.contains(&Problem::DuplicateNext("old".to_owned(),"two".to_owned())));
+		assert!(problems.iter().all(|x| !x.is_fatal()));
+		assert_eq!(log1.heads().len(),1);
+		assert!(log1.entries().contains("old\t\t>two, >two"));
+	}
+
+	#[test]
+	fn partial_log () {
+		let ipfs = ipfs();
+		let id = identity1();
+		let e1 = Entry::create(&ipfs,id.clone(),"A","one",&[],Some(LamportClock::new("x").set_time(1)));
+		let e2 = Entry::create(&ipfs,id.clone(),"A","two",&[EntryOrHash::Entry(&e1)],Some(LamportClock::new("x").set_time(2)));
+		let e3 = Entry::create(&ipfs,id.clone(),"A","three",&[EntryOrHash::Entry(&e1)],Some(LamportClock::new("y").set_time(2)));
+		let e4 = Entry::create(&ipfs,id.clone(),"A","four",&[EntryOrHash::Entry(&e2),EntryOrHash::Entry(&e3)],Some(LamportClock::new("x").set_time(3)));
+		let e5 = Entry::create(&ipfs,id.clone(),"A","five",&[EntryOrHash::Entry(&e4)],Some(LamportClock::new("x").set_time(4)));
+
+		let mut log = Log::from_multihash_partial(ipfs.clone(),id.clone(),LogOptions::new().id("A"),e5.hash(),2);
+		assert_eq!(log.len(),2);
+		assert!(!log.is_complete());
+		let mut missing = vec![e2.hash().to_owned(),e3.hash().to_owned()];
+		missing.sort();
+		assert_eq!(log.missing(),missing);
+		assert_eq!(log.load_more(1),1);
+		assert_eq!(log.len(),3);
+		assert_eq!(log.load_more(10),2);
+		assert!(log.is_complete());
+		assert_eq!(log.values().iter().map(|x| x.payload()).collect::<Vec<_>>(),vec!["one","two","three","four","five"]);
+		assert_eq!(log.load_more(10),0);
+
+		//fetched entries go through the checks of join
+		let now = HybridClock::now();
+		let f1 = Entry::create(&ipfs,id.clone(),"A","ahead",&[],Some(HybridClock::new("x").set_time(now + 2 * HybridClock::MAX_DRIFT,0)));
+		let f2 = Entry::create(&ipfs,id.clone(),"A","one",&[],Some(HybridClock::new("x").set_time(now,0)));
+		let f3 = Entry::create(&ipfs,id.clone(),"A","two",&[EntryOrHash::Entry(&f1),EntryOrHash::Entry(&f2)],Some(HybridClock::new("x").set_time(now,1)));
+		let f4 = Entry::create(&ipfs,id.clone(),"B","other",&[],Some(HybridClock::new("x").set_time(now,0)));
+		let f5 = Entry::create(&ipfs,id.clone(),"A","three",&[EntryOrHash::Entry(&f3),EntryOrHash::Entry(&f4)],Some(HybridClock::new("x").set_time(now,2)));
+		let mut log: Log<HybridClock> = Log::from_multihash_partial(ipfs.clone(),id.clone(),LogOptions::default().id("A"),f5.hash(),1);
+		assert_eq!(log.load_more(10),1);
+		assert!(log.has(f2.hash()));
+		assert!(!log.has(f1.hash()) && !log.has(f3.hash()));
+		assert!(log.is_excluded(f4.hash()));
+		assert_eq!(log.missing(),vec![f3.hash().to_owned()]);
+	}
+
+	#[test]
+	fn history () {
+		let ipfs = ipfs();
+		let id = identity1();
+		let e1 = Entry::create(&ipfs,id.clone(),"A","one",&[],Some(LamportClock::new("x").set_time(1)));
+		let e2 = Entry::create(&ipfs,id.clone(),"A","two",&[EntryOrHash::Entry(&e1)],Some(LamportClock::new("x").set_time(2)));
+		let e3 = Entry::create(&ipfs,id.clone(),"A","three",&[EntryOrHash::Entry(&e1)],Some(LamportClock::new("y").set_time(2)));
+		let e4 = Entry::create(&ipfs,id.clone(),"A","four",&[EntryOrHash::Entry(&e2),EntryOrHash::Entry(&e3)],Some(LamportClock::new("x").set_time(3)));
+		let e5 = Entry::create(&ipfs,id.clone(),"A","five",&[EntryOrHash::Entry(&e4)],Some(LamportClock::new("x").set_time(4)));
+
+		let mut log = Log::from_multihash_partial(ipfs.clone(),id.clone(),LogOptions::new().id("A"),e5.hash(),1);
+		let mut history = log.history(3);
+		let payloads = history.by_ref().map(|x| x.payload().to_owned()).collect::<Vec<_>>();
+		assert_eq!(payloads,vec!["five","four","three","two"]);
+		assert_eq!(history.budget(),0);
+		assert_eq!(log.len(),4);
+		assert_eq!(log.missing(),vec![e1.hash().to_owned()]);
+		let payloads = log.history(5).map(|x| x.payload().to_owned()).collect::<Vec<_>>();
+		assert_eq!(payloads,vec!["five","four","three","two","one"]);
+		assert!(log.is_complete());
+
+		//fetched parents go through the checks of join
+		let now = HybridClock::now();
+		let f1 = Entry::create(&ipfs,id.clone(),"A","ahead",&[],Some(HybridClock::new("x").set_time(now + 2 * HybridClock::MAX_DRIFT,0)));
+		let f2 = Entry::create(&ipfs,id.clone(),"B","other",&[],Some(HybridClock::new("x").set_time(now,0)));
+		let f3 = Entry::create(&ipfs,id.clone(),"A","one",&[],Some(HybridClock::new("x").set_time(now,0)));
+		let f4 = Entry::create(&ipfs,id.clone(),"A","two",&[EntryOrHash::Entry(&f1),EntryOrHash::Entry(&f2),EntryOrHash::Entry(&f3)],
+		Some(HybridClock::new("x").set_time(now,1)));
+		let mut log: Log<HybridClock> = Log::from_multihash_partial(ipfs.clone(),id.clone(),LogOptions::default().id("A"),f4.hash(),1);
+		let payloads = log.history(10).map(|x| x.payload().to_owned()).collect::<Vec<_>>();
+		assert_eq!(payloads,vec!["two","one"]);
+		assert!(log.is_excluded(f2.hash()));
+		assert_eq!(log.missing(),vec![f1.hash().to_owned()]);
+	}
+
+	#[test]
+	fn loaders () {
+		let ipfs = ipfs();
+		let id = identity1();
+		let e1 = Entry::create(&ipfs,id.clone(),"A","one",&[],Some(LamportClock::new("x").set_time(1)));
+		let e2 = Entry::create(&ipfs,id.clone(),"A","two",&[EntryOrHash::Entry(&e1)],Some(LamportClock::new("x").set_time(2)));
+		let e3 = Entry::create(&ipfs,id.clone(),"A","three",&[EntryOrHash::Entry(&e1)],Some(LamportClock::new("y").set_time(2)));
+		let hashes = vec![e2.hash().to_owned(),e3.hash().to_owned()];
+		let payloads = |es: Vec<Rc<Entry>>| es.iter().map(|x| x.payload().to_owned()).collect::<Vec<_>>();
+
+		let log = Log::from_heads(ipfs.clone(),id.clone(),LogOptions::new().id("A"),&hashes);
+		assert_eq!(payloads(log.values()),vec!["one","two","three"]);
+		assert_eq!(log.heads().len(),2);
+
+		let local = Rc::new(Entry::new(id.clone(),"A","local",&[EntryOrHash::Entry(&e1)],Some(LamportClock::new("z").set_time(2))));
+		let log = Log::from_entry_hashes(ipfs.clone(),id.clone(),LogOptions::new().id("A").entries(&[e1.clone(),local]),&hashes);
+		assert_eq!(payloads(log.values()),vec!["one","two","three","local"]);
+		assert_eq!(log.heads().len(),3);
+
+		let hash = Log::from_heads(ipfs.clone(),id.clone(),LogOptions::new().id("B").sort(SortStrategy::EntryHash),&hashes).to_multihash();
+		let log = Log::from_manifest(ipfs.clone(),id.clone(),LogOptions::new(),&hash).unwrap();
+		assert_eq!(log.id(),"B");
+		assert_eq!(log.sort_strategy(),&SortStrategy::EntryHash);
+		assert_eq!(log.heads().len(),2);
+		assert!(Log::from_manifest(ipfs.clone(),id.clone(),LogOptions::new().sort(SortStrategy::LastWriteWins),&hash).is_none());
+	}
+
+	#[test]
+	fn to_multihash () {
+		let ipfs = ipfs();
+		let mut log = Log::new(ipfs.clone(),identity1(),LogOptions::new().id("A"));
+		log.append("one",None);
+		log.append("two",None);
+		let hash = log.to_multihash();
+		assert_eq!(hash,log.to_multihash());
+		let log2 = Log::from_manifest(ipfs.clone(),identity1(),LogOptions::new(),&hash).unwrap();
+		assert_eq!(log.json(),log2.json());
+		assert_eq!(log.snapshot(),log2.snapshot());
+	}
+
+	#[test]
+	fn from_snapshot () {
+		let id = identity1();
+		let e1 = Entry::new(id.clone(),"A","one",&[],Some(LamportClock::new("x").set_time(1)));
+		let e2 = Entry::new(id.clone(),"A","two",&[EntryOrHash::Entry(&e1)],Some(LamportClock::new("x").set_time(2)));
+		let e3 = Entry::new(id.clone(),"A","three",&[EntryOrHash::Entry(&e1)],Some(LamportClock::new("y").set_time(5)));
+		let es = &[Rc::new(e1),Rc::new(e2),Rc::new(e3)];
+		let log = Log::new(ipfs(),id.clone(),LogOptions::new().id("A").entries(es).sort(SortStrategy::EntryHash));
+
+		let snapshot = log.snapshot();
+		let log2 = Log::from_snapshot(ipfs(),id.clone(),LogOptions::new(),&snapshot).unwrap();
+		assert_eq!(log2.id(),"A");
+		assert_eq!(log2.len(),3);
+		assert_eq!(log2.heads(),log.heads());
+		assert_eq!(log2.clock().time(),5);
+		assert_eq!(log2.sort_strategy(),&SortStrategy::EntryHash);
+		assert_eq!(log2.snapshot(),snapshot);
+		assert!(Log::from_snapshot(ipfs(),id.clone(),LogOptions::new().sort(SortStrategy::LastWriteWins),&snapshot).is_none());
+		let mut unsupported: serde_json::Value = serde_json::from_str(&snapshot).unwrap();
+		unsupported["v"] = json!(2);
+		assert!(Log::from_snapshot(ipfs(),id.clone(),LogOptions::new(),&unsupported.to_string()).is_none());
+
+		//only the entries appended after the snapshot are fetched
+		let store = Rc::new(MemoryStore::new());
+		let mut log = Log::new(store.clone(),id.clone(),LogOptions::new().id("A"));
+		log.append("one",None);
+		log.append("two",None);
+		let snapshot = log.snapshot();
+		log.append("three",None);
+		let progress = Rc::new(RefCell::new(Vec::new()));
+		let sink = progress.clone();
+		let opts = LogOptions::new().subscribe(move |e| if let Event::LoadProgress {loaded,..} = e {
+			sink.borrow_mut().push(*loaded);
+		});
+		let mut log2 = Log::from_snapshot(store,id.clone(),opts,&snapshot).unwrap();
+		assert_eq!(log2.join_heads(&[log.heads()[0].hash().to_owned()]),Some(1));
+		assert_eq!(*progress.borrow(),vec![1]);
+		assert_eq!(log2.values().iter().map(|x| x.payload()).collect::<Vec<_>>(),vec!["one","two","three"]);
+	}
+
+	#[test]
+	fn car () {
+		let id = identity1();
+		let store = Rc::new(MemoryStore::new());
+		let mut log = Log::new(store.clone(),id.clone(),LogOptions::new().id("A").sort(SortStrategy::EntryHash));
+		log.append("one",None);
+		log.append("two",None);
+		let mut log2 = Log::new(store.clone(),id.clone(),LogOptions::new().id("A").sort(SortStrategy::EntryHash));
+		log2.append("three",None);
+		log.join(&log2,None);
+
+		let mut archive = Vec::new();
+		log.export_car(&mut archive).unwrap();
+		let (roots,blocks) = car::read_car(&archive[..]).unwrap();
+		assert_eq!(roots.len(),2);
+		assert_eq!(blocks.len(),4);
+
+		let store = Rc::new(MemoryStore::new());
+		let log3 = Log::import_car(store.clone(),id.clone(),LogOptions::new(),&archive[..]).unwrap();
+		assert_eq!(store.len(),4);
+		assert_eq!(log3.id(),"A");
+		assert_eq!(log3.sort_strategy(),&SortStrategy::EntryHash);
+		assert_eq!(log3.json(),log.json());
+		assert_eq!(log3.values(),log.values());
+
+		//a corrupted block
+		let n = archive.len();
+		archive[n - 2] ^= 1;
+		assert!(car::read_car(&archive[..]).is_err());
+		assert!(Log::<LamportClock>::import_car(Rc::new(MemoryStore::new()),id.clone(),LogOptions::new(),&archive[..]).is_err());
+
+		//roots other than the heads of the manifest
+		let mut archive = Vec::new();
+		car::write_car(&mut archive,&roots[..1],&blocks).unwrap();
+		assert!(Log::<LamportClock>::import_car(Rc::new(MemoryStore::new()),id.clone(),LogOptions::new(),&archive[..]).is_err());
+
+		//an entry of another log
+		let store = Rc::new(MemoryStore::new());
+		let other = Entry::create(&*store,id.clone(),"B","other",&[],Some(LamportClock::new("x")));
+		let one = Entry::create(&*store,id.clone(),"A","one",&[EntryOrHash::Entry(&other)],Some(LamportClock::new("x").set_time(1)));
+		let manifest = json!({"id": "A", "heads": [one.hash()]}).to_string().into_bytes();
+		let mut blocks = Vec::new();
+		for h in &[other.hash(),one.hash()] {
+			let data = Runtime::new().unwrap().block_on(store.cat(h)).unwrap();
+			blocks.push((car::cid(&data),data));
+		}
+		let roots = vec![blocks[1].0.clone()];
+		blocks.push((car::cid(&manifest),manifest));
+		let mut archive = Vec::new();
+		car::write_car(&mut archive,&roots,&blocks).unwrap();
+		assert!(Log::<LamportClock>::import_car(Rc::new(MemoryStore::new()),id.clone(),LogOptions::new(),&archive[..]).is_err());
+	}
+
+	#[test]
+	fn checkpoints () {
+		let mut idpr = DefaultIdentificator::new();
+		let a = idpr.create_from_seed("a",b"a");
+		let b = idpr.create_from_seed("b",b"b");
+		let store = Rc::new(MemoryStore::new());
+		let mut log = Log::new(store.clone(),a.clone(),LogOptions::new().id("A").key(LogKey::generate()));
+		log.add_readers(&[a.pub_key(),b.pub_key()]).unwrap();
+		log.append("one",None);
+		log.append("two",None);
+		assert_eq!(log.prune(),0);
+		log.checkpoint("state:2");
+		log.append("three",None);
+		let full = log.values();
+		let readers = log.readers();
+		assert_eq!(log.checkpoint_state().unwrap(),"state:2");
+
+		assert_eq!(log.prune(),3);
+		assert_eq!(log.len(),2);
+		assert!(log.is_complete());
+		assert!(log.validate().iter().all(|p| !p.is_fatal()));
+		assert_eq!(log.payload(&log.values()[1]).unwrap(),"three");
+		assert_eq!(log.readers(),readers);
+		let full = Log::new(store.clone(),a.clone(),LogOptions::new().id("A").entries(&full));
+		log.join(&full,None);
+		assert_eq!(log.len(),2);
+
+		//replicas start from the checkpoint
+		let hs = log.heads().iter().map(|x| x.hash().to_owned()).collect::<Vec<_>>();
+		let replica = Log::<LamportClock>::from_heads(store.clone(),b.clone(),LogOptions::new().id("A"),&hs);
+		assert_eq!(replica.len(),2);
+		assert!(replica.is_complete());
+		assert_eq!(replica.latest_checkpoint(),log.latest_checkpoint());
+		assert_eq!(replica.readers(),log.readers());
+
+		//entries before the checkpoint reached through a concurrent branch
+		let mut log = Log::new(store.clone(),a.clone(),LogOptions::new().id("B"));
+		log.append("one",None);
+		log.append("two",None);
+		let mut other = Log::new(store.clone(),b.clone(),LogOptions::new().id("B"));
+		other.join(&log,None);
+		other.append("concurrent",None);
+		log.checkpoint("state:2");
+		log.append("three",None);
+		log.join(&other,None);
+		let hs = log.heads().iter().map(|x| x.hash().to_owned()).collect::<Vec<_>>();
+		let mut replica = Log::<LamportClock>::from_heads(store.clone(),b.clone(),LogOptions::new().id("B"),&hs);
+		assert_eq!(replica.len(),5);
+		assert_eq!(replica.prune(),2);
+		assert_eq!(replica.values().iter().map(|x| x.payload()).filter(|x| !x.starts_with('{')).collect::<Vec<_>>(),vec!["concurrent","three"]);
+		assert!(replica.is_complete());
+	}
+
+	#[test]
+	fn pinning () {
+		let id = identity1();
+		let store = Rc::new(MemoryStore::new());
+		let mut log = Log::new(store.clone(),id.clone(),LogOptions::new().id("A").pinning(PinningPolicy::Last(2)));
+		log.append("one",None);
+		log.append("two",None);
+		log.append("three",None);
+		let es = log.values();
+		assert_eq!(es.iter().map(|x| store.is_pinned(x.hash())).collect::<Vec<_>>(),vec![false,true,true]);
+
+		let mut log2 = Log::new(store.clone(),id.clone(),LogOptions::new().id("A").pinning(PinningPolicy::Heads));
+		log2.join(&log,None);
+		log2.append("four",None);
+		//still pinned by the first log
+		assert!(store.is_pinned(es[2].hash()));
+		assert!(store.is_pinned(log2.heads()[0].hash()));
+		log2.join(&log,Some(1));
+		assert_eq!(log2.pinning(),PinningPolicy::Heads);
+
+		log.checkpoint("state");
+		log.prune();
+		assert!(log.values().iter().all(|x| store.is_pinned(x.hash())));
+		assert!(!store.is_pinned(es[2].hash()));
+		assert_eq!(store.gc(),3);
+		assert!(Runtime::new().unwrap().block_on(store.cat(log2.heads()[0].hash())).is_ok());
+
+		//appending moves the window of the most recent entries along, also after a join
+		let store = Rc::new(MemoryStore::new());
+		let mut log = Log::new(store.clone(),id.clone(),LogOptions::new().id("A").pinning(PinningPolicy::Last(2)));
+		let mut other = Log::new(store.clone(),id.clone(),LogOptions::new().id("A"));
+		other.append("one",None);
+		other.append("two",None);
+		log.join(&other,None);
+		log.append("three",None);
+		log.append("four",None);
+		let pinned = |log: &Log| log.values().iter().map(|x| store.is_pinned(x.hash())).collect::<Vec<_>>();
+		assert_eq!(pinned(&log),vec![false,false,true,true]);
+		let mut log = Log::new(store.clone(),id.clone(),LogOptions::new().id("A").pinning(PinningPolicy::All));
+		log.append("five",None);
+		log.append("six",None);
+		assert_eq!(pinned(&log),vec![true,true]);
+		let mut log = Log::new(store.clone(),id.clone(),LogOptions::new().id("A").pinning(PinningPolicy::Last(0)));
+		log.append("seven",None);
+		assert_eq!(pinned(&log),vec![false]);
+	}
+
+	#[test]
+	fn events () {
+		let id = identity1();
+		let store = Rc::new(MemoryStore::new());
+		let events = Rc::new(RefCell::new(Vec::new()));
+		let mut log = Log::new(store.clone(),id.clone(),LogOptions::new().id("A"));
+		let sink = events.clone();
+		let subscription = log.subscribe(move |e| sink.borrow_mut().push(match e {
+			Event::Appended(e)						=>	format!("appended {}",e.payload()),
+			Event::Joined {new_entries,new_heads}	=>	format!("joined {} {}",new_entries.len(),new_heads.len()),
+			Event::LoadProgress {loaded,total_hint}	=>	format!("loaded {} {:?}",loaded,total_hint),
+			Event::Truncated {removed}				=>	format!("truncated {}",removed.len()),
+		}));
+		log.append("one",None);
+		let mut log2 = Log::new(store.clone(),id.clone(),LogOptions::new().id("A"));
+		log2.append("two",None);
+		log2.append("three",None);
+		log.join(&log2,Some(2));
+		log.join(&log2,None);
+		assert_eq!(*events.borrow(),vec!["appended one","joined 2 1","truncated 1"]);
+
+		assert!(log.unsubscribe(subscription));
+		assert!(!log.unsubscribe(subscription));
+		log.append("four",None);
+		assert_eq!(events.borrow().len(),3);
+
+		//joined entries truncated right away are neither joined nor removed
+		let mut log4 = Log::new(store.clone(),id.clone(),LogOptions::new().id("A"));
+		log4.append("five",None);
+		let old = Entry::create(&*store,id.clone(),"A","old",&[],Some(LamportClock::new("x").set_time(0)));
+		let log5 = Log::new(store.clone(),id.clone(),LogOptions::new().id("A").entries(&[old]));
+		let sink = events.clone();
+		log4.subscribe(move |e| sink.borrow_mut().push(format!("{:?}",e)));
+		log4.join(&log5,Some(1));
+		assert_eq!(log4.values()[0].payload(),"five");
+		assert_eq!(events.borrow().len(),3);
+
+		//the progress of loading a log
+		let progress = Rc::new(RefCell::new(Vec::new()));
+		let sink = progress.clone();
+		let opts = LogOptions::new().id("A").subscribe(move |e| if let Event::LoadProgress {loaded,..} = e {
+			sink.borrow_mut().push(*loaded);
+		});
+		let log3 = Log::from_multihash(store.clone(),id.clone(),opts,log.heads()[0].hash());
+		assert_eq!(log3.len(),3);
+		assert_eq!(*progress.borrow(),vec![1,2,3]);
+	}
+
+	#[test]
+	fn reconcile () {
+		use std::sync::mpsc::channel;
+
+		let id = identity1();
+		let store = Rc::new(MemoryStore::new());
+		let mut a = Log::new(store.clone(),id.clone(),LogOptions::new().id("A"));
+		a.append("one",None);
+		a.append("two",None);
+		let mut b = Log::new(store.clone(),id.clone(),LogOptions::new().id("A"));
+		b.join(&a,None);
+		a.append("three",None);
+		a.append("four",None);
+		b.append("five",None);
+		let three = a.values()[2].hash().to_owned();
+		let filter = BloomFilter::new(vec![&three[..]]);
+		assert!(filter.contains(&three));
+		assert!(!filter.contains(a.values()[3].hash()));
+
+		//messages are passed as JSON over channels, optionally tampering with the filters of b
+		let sketched = RefCell::new(Vec::new());
+		let reconcile = |tamper: Option<&BloomFilter>| {
+			let (to_a,from_b) = channel::<String>();
+			let (to_b,from_a) = channel::<String>();
+			let (mut ra,mut rb) = (Reconciler::new(),Reconciler::new());
+			to_b.send(serde_json::to_string(&ra.start(&a)).unwrap()).unwrap();
+			loop {
+				let mut quiet = true;
+				for m in from_a.try_iter() {
+					quiet = false;
+					for r in rb.handle(&b,serde_json::from_str(&m).unwrap()) {
+						let r = match (r,tamper) {
+							(Message::Sketch {heads,frontier,filter},tamper)	=>	{
+								sketched.borrow_mut().push(filter.clone());
+								Message::Sketch {
+									heads,
+									frontier,
+									filter: tamper.cloned().unwrap_or(filter),
+								}
+							},
+							(r,_)												=>	r,
+						};
+						to_a.send(serde_json::to_string(&r).unwrap()).unwrap();
+					}
+				}
+				for m in from_b.try_iter() {
+					quiet = false;
+					for r in ra.handle(&a,serde_json::from_str(&m).unwrap()) {
+						to_b.send(serde_json::to_string(&r).unwrap()).unwrap();
+					}
+				}
+				if quiet {
+					break;
+				}
+			}
+			assert!(ra.is_done() && rb.is_done());
+			(ra.missing(),rb.missing())
+		};
+
+		let hashes = |log: &Log,payloads: &[&str]| payloads.iter().
+		map(|p| log.values().iter().find(|x| x.payload() == *p).unwrap().hash().to_owned()).collect::<Vec<_>>();
+		let (missing_a,missing_b) = reconcile(None);
+		assert_eq!(missing_a,hashes(&b,&["five"]));
+		assert_eq!(missing_b,hashes(&a,&["three","four"]));
+		//neither knows the heads of the other, but the frontier of a leads b to their common entries
+		assert_eq!(*sketched.borrow(),vec![BloomFilter::new(hashes(&b,&["five"]).iter().map(|x| &x[..]))]);
+		//a false positive in the filter of b withholds an entry, which is then asked for
+		let (missing_a,missing_b) = reconcile(Some(&filter));
+		assert_eq!(missing_a,hashes(&b,&["five"]));
+		assert_eq!(missing_b,hashes(&a,&["three","four"]));
+		//a false positive on a head of a withholds an entry no other entry points to
+		let filter = BloomFilter::new(vec![a.values()[3].hash()]);
+		let (missing_a,missing_b) = reconcile(Some(&filter));
+		assert_eq!(missing_a,hashes(&b,&["five"]));
+		assert_eq!(missing_b,hashes(&a,&["three","four"]));
+
+		let b = Log::from_entry_hashes(store.clone(),id.clone(),LogOptions::new().id("A").entries(&b.values()),&missing_b);
+		assert_eq!(b.len(),5);
+		assert!(b.is_complete());
+	}
+
+	#[test]
+	fn replicator () {
+		let id = identity1();
+		let store = Rc::new(MemoryStore::new());
+		let network = MemoryNetwork::new(1);
+		let mut a = Log::new(store.clone(),id.clone(),LogOptions::new().id("A"));
+		let mut b = Log::new(store.clone(),id.clone(),LogOptions::new().id("A"));
+		let mut ra = Replicator::new(network.connect("a"),4);
+		let mut rb = Replicator::new(network.connect("b"),4);
+		ra.add_peer("b");
+		rb.add_peer("a");
+
+		a.append("one",None);
+		a.append("two",None);
+		ra.announce(&a);
+		assert_eq!(rb.poll(&mut b).unwrap(),2);
+		assert_eq!(b.values().iter().map(|x| x.payload()).collect::<Vec<_>>(),vec!["one","two"]);
+		assert_eq!(ra.poll(&mut a).unwrap(),0);
+
+		//the inbox of b holds a single message, the rest wait in the queue of a
+		a.append("three",None);
+		ra.announce(&a);
+		a.append("four",None);
+		ra.announce(&a);
+		assert_eq!(ra.pending("b"),1);
+		assert_eq!(rb.poll(&mut b).unwrap(),1);
+		ra.poll(&mut a).unwrap();
+		assert_eq!(ra.pending("b"),0);
+		assert_eq!(rb.poll(&mut b).unwrap(),1);
+		assert_eq!(b.heads(),a.heads());
+
+		//announcements of other logs are ignored
+		let mut other = Log::new(store.clone(),id.clone(),LogOptions::new().id("B"));
+		other.append("five",None);
+		ra.announce(&other);
+		assert_eq!(rb.poll(&mut b).unwrap(),0);
+		assert_eq!(b.len(),4);
+
+		//heads that are not found or are not entries are skipped
+		let mut c = network.connect("c");
+		let block = Runtime::new().unwrap().block_on(store.add(b"not an entry".to_vec())).unwrap();
+		let bogus = json!({"id": "A","heads": ["QmBogus",block]}).to_string();
+		c.send("b",bogus.as_bytes()).unwrap();
+		assert_eq!(rb.poll(&mut b).unwrap(),0);
+		assert_eq!(b.len(),4);
+		assert_eq!(b.join_heads(&["QmBogus".to_owned()]),Some(0));
+	}
+
+	#[test]
+	fn tcp_transport () {
+		let id = identity1();
+		let store = Rc::new(MemoryStore::new());
+		let mut a = Log::new(store.clone(),id.clone(),LogOptions::new().id("A"));
+		let mut b = Log::new(store.clone(),id.clone(),LogOptions::new().id("A"));
+		let mut ra = Replicator::new(TcpTransport::bind("127.0.0.1:0").unwrap(),4);
+		let mut rb = Replicator::new(TcpTransport::bind("127.0.0.1:0").unwrap(),4);
+		let (address_a,address_b) = (ra.transport().address().to_owned(),rb.transport().address().to_owned());
+		ra.add_peer(&address_b);
+		rb.add_peer(&address_a);
+
+		a.append("one",None);
+		ra.announce(&a);
+		b.append("two",None);
+		rb.announce(&b);
+		for _ in 0..200 {
+			ra.poll(&mut a).unwrap();
+			rb.poll(&mut b).unwrap();
+			if a.len() == 2 && b.len() == 2 {
+				break;
+			}
+			std::thread::sleep(std::time::Duration::from_millis(10));
+		}
+		let heads = |log: &Log| {
+			let mut hs = log.heads().iter().map(|x| x.hash().to_owned()).collect::<Vec<_>>();
+			hs.sort();
+			hs
+		};
+		assert_eq!(a.len(),2);
+		assert_eq!(heads(&b),heads(&a));
+
+		//a peer not reading makes sending fail with WouldBlock rather than block
+		let mut c = TcpTransport::bind("127.0.0.1:0").unwrap();
+		let d = TcpTransport::bind("127.0.0.1:0").unwrap();
+		let data = vec![0; 1 << 20];
+		let blocked = (0..256).map(|_| c.send(d.address(),&data)).find_map(|x| x.err()).unwrap();
+		assert_eq!(blocked.kind(),std::io::ErrorKind::WouldBlock);
+		let too_long = c.send(d.address(),&vec![0; MAX_FRAME + 1]).unwrap_err();
+		assert_eq!(too_long.kind(),std::io::ErrorKind::InvalidInput);
+
+		//messages are attributed to their connection, whatever the address the sender claims
+		let mut d = TcpTransport::bind("127.0.0.1:0").unwrap();
+		let mut stream = std::net::TcpStream::connect(d.address()).unwrap();
+		stream.write_all(&[0,0,0,5]).unwrap();
+		stream.write_all(b"hello").unwrap();
+		let mut received = None;
+		for _ in 0..200 {
+			received = d.recv().unwrap();
+			if received.is_some() {
+				break;
+			}
+			std::thread::sleep(std::time::Duration::from_millis(10));
+		}
+		assert_eq!(received,Some((stream.local_addr().unwrap().to_string(),b"hello".to_vec())));
+		//a frame over the maximum length closes the connection
+		stream.write_all(&((MAX_FRAME + 1) as u32).to_be_bytes()).unwrap();
+		stream.set_read_timeout(Some(std::time::Duration::from_millis(10))).unwrap();
+		let mut closed = false;
+		for _ in 0..200 {
+			assert_eq!(d.recv().unwrap(),None);
+			closed = match stream.read(&mut [0; 1]) {
+				Ok(n)	=>	n == 0,
+				Err(e)	=>	e.kind() != std::io::ErrorKind::WouldBlock && e.kind() != std::io::ErrorKind::TimedOut,
+			};
+			if closed {
+				break;
+			}
+		}
+		assert!(closed);
+
+		//connections over the maximum are closed right away
+		let streams = (0..MAX_CONNECTIONS + 1).map(|_| std::net::TcpStream::connect(d.address()).unwrap()).collect::<Vec<_>>();
+		let mut last = streams.last().unwrap();
+		last.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+		for _ in 0..20 {
+			d.recv().unwrap();
+			std::thread::sleep(std::time::Duration::from_millis(10));
+		}
+		assert_eq!(last.read(&mut [0; 1]).unwrap(),0);
+		let mut first = &streams[0];
+		first.set_read_timeout(Some(std::time::Duration::from_millis(10))).unwrap();
+		assert!(first.read(&mut [0; 1]).is_err());
+	}
+
+	#[test]
+	fn file_cache () {
+		let dir = std::env::temp_dir().join(format!("ipfs-log-rs-cache-{}",LogKey::generate().to_hex()));
+		let id = identity1();
+		let remote = Rc::new(MemoryStore::new());
+		let cache = Rc::new(FileCache::open(remote.clone(),&dir,1 << 20).unwrap());
+		let mut log = Log::new(cache.clone(),id.clone(),LogOptions::new().id("A"));
+		log.append("one",None);
+		log.append("two",None);
+		assert_eq!(remote.len(),2);
+		assert!(cache.contains(log.heads()[0].hash()));
+
+		//restarting without the remote store
+		let cache = Rc::new(FileCache::open(MemoryStore::new(),&dir,1 << 20).unwrap());
+		let log2 = Log::from_cached_heads(cache.clone(),id.clone(),LogOptions::new(),"A").unwrap();
+		assert_eq!(log2.values().iter().map(|x| x.payload()).collect::<Vec<_>>(),vec!["one","two"]);
+		assert!(Log::<LamportClock>::from_cached_heads(cache.clone(),id.clone(),LogOptions::new(),"B").is_none());
+
+		//evicting the least recently used blocks
+		let one = log.values()[0].hash().to_owned();
+		let two = log.values()[1].hash().to_owned();
+		let dir2 = dir.join("small");
+		let cache = FileCache::open(remote.clone(),&dir2,cache.size() - 1).unwrap();
+		//blocks are cached once the future of the store behind the cache resolves
+		let fetching = cache.cat(&one);
+		assert!(!cache.contains(&one));
+		assert!(Runtime::new().unwrap().block_on(fetching).is_ok());
+		assert!(Runtime::new().unwrap().block_on(cache.cat(&two)).is_ok());
+		assert!(!cache.contains(&one));
+		assert!(cache.contains(&two));
+		assert!(Runtime::new().unwrap().block_on(cache.cat(&one)).is_ok());
+		assert!(cache.contains(&one));
+		assert!(!cache.contains(&two));
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
 }
@@ -1,32 +1,66 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::BTreeSet;
+use std::collections::VecDeque;
 use std::cmp::Ordering;
 use std::cmp::max;
 use std::time::SystemTime;
 use std::rc::Rc;
+use std::cell::Cell;
 use std::fmt::{Display,Formatter,Result};
+use std::io;
+use std::io::{Read,Write};
+use std::result::Result as StdResult;
 use tokio::runtime::Runtime;
-use ipfs_api::IpfsClient;
+use serde::{Serialize,Deserialize};
 use serde_json::json;
 use crate::entry::Entry;
 use crate::entry::EntryOrHash;
 use crate::identity::Identity;
+use crate::identity::Identificator;
+use crate::clock::Clock;
 use crate::lamport_clock::LamportClock;
+use crate::control::{Control,KeyRotation,Revocation,KeyEpoch,Checkpoint,KeyRegistry};
+use crate::manifest::SignedManifest;
+use crate::crypto::{LogKey,Sealed};
+use crate::sort::SortStrategy;
+use crate::validation::{self,Problem};
+use crate::store::{Store,StoreError,PinningPolicy};
+use crate::car;
+use crate::event::Event;
 
 /// An immutable, operation-based conflict-free replicated data type ([CRDT]).
 ///
+/// Entries are timestamped with a [Lamport clock] by default,
+/// any other [clock] can be used via the type parameter `C`.
+///
 /// [CRDT]: https://en.wikipedia.org/wiki/Conflict-free_replicated_data_type
-pub struct Log {
-	ipfs: Rc<IpfsClient>,
+/// [Lamport clock]: ../lamport_clock/struct.LamportClock.html
+/// [clock]: ../clock/trait.Clock.html
+pub struct Log<C = LamportClock> {
+	store: Rc<dyn Store>,
 	id: String,
 	identity: Identity,
 	access: AdHocAccess,
-	entries: HashMap<String,Rc<Entry>>,
+	keys: KeyRegistry,
+	entries: HashMap<String,Rc<Entry<C>>>,
 	length: usize,
-	heads: Vec<Rc<Entry>>,
+	heads: Vec<Rc<Entry<C>>>,
 	nexts: HashSet<String>,
-	fn_sort: Box<dyn Fn(&Entry,&Entry) -> Ordering>,
-	clock: LamportClock,
+	sort: SortStrategy<C>,
+	fn_sort: Box<dyn Fn(&Entry<C>,&Entry<C>) -> Ordering>,
+	clock: C,
+	key: Option<LogKey>,
+	keyring: HashMap<String,LogKey>,
+	generations: HashMap<String,u64>,
+	children: HashMap<String,HashSet<String>>,
+	excluded: HashSet<String>,
+	pinning: PinningPolicy,
+	pinned: HashSet<String>,
+	//the entries pinned by PinningPolicy::Last, oldest first
+	window: VecDeque<String>,
+	subscribers: Vec<(usize,Box<dyn Fn(&Event<C>)>)>,
+	next_subscription: usize,
 }
 
 /// Options for constructing [`Log`].
@@ -34,8 +68,10 @@ pub struct Log {
 /// Constructing log options using `LogOptions::new()` creates default log options:
 /// * no identifier,
 /// * no entries (and no heads among those non-existent entries),
-/// * no Lamport clock,
-/// * no sorting algorithm.
+/// * no clock,
+/// * no sorting algorithm ([last write wins]),
+/// * no encryption key,
+/// * no [pinning].
 ///
 /// Use method chaining to set additional parameters:
 ///
@@ -44,14 +80,27 @@ pub struct Log {
 /// let log = Log::new(/* identity */,opts);
 /// ```
 ///
+/// `LogOptions::new()` constructs options for a log using Lamport clocks.
+/// For logs using other [clocks], construct the options with `LogOptions::default()`:
+///
+/// ```ignore
+/// let opts = LogOptions::<VectorClock>::default().id("some_id");
+/// ```
+///
 /// [`Log`]: ./struct.Log.html
-pub struct LogOptions<'a> {
+/// [last write wins]: ../sort/enum.SortStrategy.html#variant.LastWriteWins
+/// [clocks]: ../clock/trait.Clock.html
+/// [pinning]: ../store/enum.PinningPolicy.html
+pub struct LogOptions<'a,C = LamportClock> {
 	id: Option<&'a str>,
 	access: AdHocAccess,
-	entries: &'a[Rc<Entry>],
-	heads: &'a[Rc<Entry>],
-	clock: Option<LamportClock>,
-	fn_sort: Option<Box<dyn Fn(&Entry,&Entry) -> Ordering>>,
+	entries: &'a[Rc<Entry<C>>],
+	heads: &'a[Rc<Entry<C>>],
+	clock: Option<C>,
+	sort: Option<SortStrategy<C>>,
+	key: Option<LogKey>,
+	pinning: PinningPolicy,
+	subscribers: Vec<Box<dyn Fn(&Event<C>)>>,
 }
 
 impl<'a> LogOptions<'a> {
@@ -59,11 +108,13 @@ impl<'a> LogOptions<'a> {
 	pub fn new () -> LogOptions<'a> {
 		LogOptions::default()
 	}
+}
 
+impl<'a,C: Clock> LogOptions<'a,C> {
 	/// Sets the identifier for the constructed log options.
 	///
 	/// Allows method chaining.
-	pub fn id (mut self, id: &'a str) -> LogOptions {
+	pub fn id (mut self, id: &'a str) -> LogOptions<'a,C> {
 		self.id = Some(id);
 		self
 	}
@@ -71,7 +122,7 @@ impl<'a> LogOptions<'a> {
 	/// Sets the entries for the constructed log options.
 	///
 	/// Allows method chaining.
-	pub fn entries (mut self, es: &'a[Rc<Entry>]) -> LogOptions {
+	pub fn entries (mut self, es: &'a[Rc<Entry<C>>]) -> LogOptions<'a,C> {
 		self.entries = es;
 		self
 	}
@@ -79,30 +130,84 @@ impl<'a> LogOptions<'a> {
 	/// Sets the heads for the constructed log options.
 	///
 	/// Allows method chaining.
-	pub fn heads (mut self, hs: &'a[Rc<Entry>]) -> LogOptions {
+	pub fn heads (mut self, hs: &'a[Rc<Entry<C>>]) -> LogOptions<'a,C> {
 		self.heads = hs;
 		self
 	}
 
-	/// Sets the Lamport clock for the constructed log options.
+	/// Sets the clock for the constructed log options.
 	///
 	/// Allows method chaining.
-	pub fn clock (mut self, clock: LamportClock) -> LogOptions<'a> {
+	pub fn clock (mut self, clock: C) -> LogOptions<'a,C> {
 		self.clock = Some(clock);
 		self
 	}
 
 	/// Sets the sorting algorithm for the constructed log options.
 	///
+	/// The algorithm is set as an unnamed [custom strategy]. As replicas cannot tell whether
+	/// they use the same unnamed strategy, a log using one refuses to [`join`] other logs,
+	/// use [`sort`] to register the algorithm under a name instead.
+	///
+	/// Allows method chaining.
+	///
+	/// [custom strategy]: ../sort/enum.SortStrategy.html#variant.Custom
+	/// [`join`]: ./struct.Log.html#method.join
+	/// [`sort`]: #method.sort
+	pub fn fn_sort<F> (mut self, fn_sort: F) -> LogOptions<'a,C>
+	where F: 'static + Fn(&Entry<C>,&Entry<C>) -> Ordering {
+		self.sort = Some(SortStrategy::Custom(String::new(),Rc::new(fn_sort)));
+		self
+	}
+
+	/// Sets the [sorting strategy] for the constructed log options.
+	///
+	/// Allows method chaining.
+	///
+	/// [sorting strategy]: ../sort/enum.SortStrategy.html
+	pub fn sort (mut self, sort: SortStrategy<C>) -> LogOptions<'a,C> {
+		self.sort = Some(sort);
+		self
+	}
+
+	/// Sets the key to encrypt and decrypt entry payloads with for the constructed log options.
+	///
+	/// Allows method chaining.
+	pub fn key (mut self, key: LogKey) -> LogOptions<'a,C> {
+		self.key = Some(key);
+		self
+	}
+
+	/// Sets the [pinning policy] for the constructed log options.
+	///
 	/// Allows method chaining.
-	pub fn fn_sort<F> (mut self, fn_sort: F) -> LogOptions<'a>
-	where F: 'static + Fn(&Entry,&Entry) -> Ordering {
-		self.fn_sort = Some(Box::new(fn_sort));
+	///
+	/// [pinning policy]: ../store/enum.PinningPolicy.html
+	pub fn pinning (mut self, pinning: PinningPolicy) -> LogOptions<'a,C> {
+		self.pinning = pinning;
 		self
 	}
+
+	/// Subscribes `f` to the [events] of the constructed log, including the progress
+	/// of fetching its entries when it is loaded from a store.
+	///
+	/// Allows method chaining.
+	///
+	/// [events]: ../event/enum.Event.html
+	pub fn subscribe<F> (mut self, f: F) -> LogOptions<'a,C>
+	where F: 'static + Fn(&Event<C>) {
+		self.subscribers.push(Box::new(f));
+		self
+	}
+
+	fn emit (&self, event: Event<C>) {
+		for f in &self.subscribers {
+			f(&event);
+		}
+	}
 }
 
-impl<'a> Default for LogOptions<'a> {
+impl<'a,C: Clock> Default for LogOptions<'a,C> {
 	fn default () -> Self {
 		LogOptions {
 			id: None,
@@ -110,21 +215,25 @@ impl<'a> Default for LogOptions<'a> {
 			entries: &[],
 			heads: &[],
 			clock: None,
-			fn_sort: None,
+			sort: None,
+			key: None,
+			pinning: PinningPolicy::None,
+			subscribers: Vec::new(),
 		}
 	}
 }
 
-impl Log {
+impl<C: Clock> Log<C> {
 	/// Constructs a new log owned by `identity`, using `opts` for constructor options.
 	///
 	/// Use [`LogOptions::new()`] as `opts` for default constructor options.
 	///
 	/// [`LogOptions::new()`]: ./struct.LogOptions.html#method.new
-	pub fn new (ipfs: Rc<IpfsClient>, identity: Identity, opts: LogOptions) -> Log {
-		let (id, access, entries, heads, clock, fn_sort) =
-		(opts.id, opts.access, opts.entries, opts.heads, opts.clock, opts.fn_sort);
-		let fn_sort = Box::new(Entry::no_zeroes(fn_sort.unwrap_or(Box::new(Entry::last_write_wins))));
+	pub fn new (store: Rc<dyn Store>, identity: Identity, opts: LogOptions<C>) -> Log<C> {
+		let (id, access, entries, heads, clock, sort, key, pinning, subscribers) =
+		(opts.id, opts.access, opts.entries, opts.heads, opts.clock, opts.sort, opts.key, opts.pinning, opts.subscribers);
+		let sort = sort.unwrap_or(SortStrategy::LastWriteWins);
+		let fn_sort = Box::new(Entry::no_zeroes(sort.function()));
 		let id = if let Some(s) = id {
 			s.to_owned()
 		}
@@ -152,27 +261,50 @@ impl Log {
 			entry_set.insert(e.hash().to_owned(),e.clone());
 		}
 
-		let mut t_max = 0;
+		let mut keys = KeyRegistry::new();
+		keys.register(entries.iter().map(|x| &**x));
+
+		let mut keyring = HashMap::new();
+		if let Some(ref k) = key {
+			keyring.insert(k.id(),k.clone());
+		}
+
+		let mut t_max = C::new(identity.pub_key());
 		if let Some(c) = clock {
-			t_max = c.time();
+			t_max.merge(&c);
 		}
 		for h in &heads {
-			t_max = max(t_max,h.clock().time());
+			t_max.merge(h.clock());
 		}
-		let clock = LamportClock::new(identity.pub_key()).set_time(t_max);
+		let clock = t_max;
 
-		Log {
-			ipfs: ipfs.clone(),
+		let mut log = Log {
+			store: store.clone(),
 			id: id,
 			identity: identity,
 			access: access,
+			keys: keys,
 			entries: entry_set,
 			length: length,
 			heads: heads,
 			nexts: nexts,
+			sort: sort,
 			fn_sort: fn_sort,
 			clock: clock,
-		}
+			key: key,
+			keyring: keyring,
+			generations: HashMap::new(),
+			children: HashMap::new(),
+			excluded: HashSet::new(),
+			pinning: pinning,
+			pinned: HashSet::new(),
+			window: VecDeque::new(),
+			next_subscription: subscribers.len(),
+			subscribers: subscribers.into_iter().enumerate().collect(),
+		};
+		log.reindex();
+		log.repin();
+		log
 	}
 
 	/// Constructs a new log with the identity `identity` from an entry with the hash `hash`,
@@ -185,21 +317,156 @@ impl Log {
 	/// [`LogOptions::new()`]: ./struct.LogOptions.html#method.new
 	/// [`opts.entries(/* entries */)`]: ./struct.LogOptions.html#method.entries
 	/// [`opts.heads(/* heads */)`]: ./struct.LogOptions.html#method.heads
-	pub fn from_multihash (ipfs: Rc<IpfsClient>, identity: Identity, opts: LogOptions, hash: &str) -> Log {
-		let es = Entry::fetch_entries(&ipfs,&[hash.to_owned()]).into_iter().map(|x| Rc::new(x)).collect::<Vec<Rc<Entry>>>();
-		Log::new(ipfs,identity,opts.entries(&es).heads(&[]))
+	pub fn from_multihash (store: Rc<dyn Store>, identity: Identity, opts: LogOptions<C>, hash: &str) -> Log<C> {
+		Log::from_heads(store,identity,opts,&[hash.to_owned()])
+	}
+
+	/// Constructs a new log with the identity `identity` from the entries with the hashes `hashes`,
+	/// using `opts` for constructor options.
+	///
+	/// Use [`LogOptions::new()`] as `opts` for default constructor options.
+	///
+	/// **N.B.** [`opts.entries(/* entries */)`] *and* [`opts.heads(/* heads */)`] *have no effect in the log created.*
+	///
+	/// [`LogOptions::new()`]: ./struct.LogOptions.html#method.new
+	/// [`opts.entries(/* entries */)`]: ./struct.LogOptions.html#method.entries
+	/// [`opts.heads(/* heads */)`]: ./struct.LogOptions.html#method.heads
+	pub fn from_heads (store: Rc<dyn Store>, identity: Identity, opts: LogOptions<C>, hashes: &[String]) -> Log<C> {
+		let es = Log::fetch(&store,hashes,None,&HashSet::new(),&|n| opts.emit(Event::LoadProgress {
+			loaded: n,
+			total_hint: None,
+		}));
+		Log::new(store,identity,opts.entries(&es).heads(&[]))
+	}
+
+	/// Constructs a new log with the identity `identity` from the entries with the hashes `hashes`
+	/// and the local entries set with [`opts.entries(/* entries */)`], using `opts` for constructor options.
+	///
+	/// Only the entries not among the local entries are fetched.
+	///
+	/// **N.B.** [`opts.heads(/* heads */)`] *has no effect in the log created.*
+	///
+	/// [`opts.entries(/* entries */)`]: ./struct.LogOptions.html#method.entries
+	/// [`opts.heads(/* heads */)`]: ./struct.LogOptions.html#method.heads
+	pub fn from_entry_hashes (store: Rc<dyn Store>, identity: Identity, opts: LogOptions<C>, hashes: &[String]) -> Log<C> {
+		let local = opts.entries.iter().map(|x| x.hash().to_owned()).collect();
+		let fetched = Log::fetch(&store,hashes,None,&local,&|n| opts.emit(Event::LoadProgress {
+			loaded: n,
+			total_hint: None,
+		}));
+		let es = opts.entries.iter().map(|x| x.clone()).chain(fetched.into_iter()).collect::<Vec<_>>();
+		Log::new(store,identity,opts.entries(&es).heads(&[]))
+	}
+
+	/// Constructs a new log with the identity `identity` from the manifest stored with the hash `hash`,
+	/// i.e. the output of [`json`], using `opts` for constructor options.
+	/// The identifier of the log is set to that of the manifest.
+	///
+	/// The [sorting strategy] of the log is set to that of the manifest, unless set in `opts`.
+	/// Returns `None` if the manifest is not found, the strategy set in `opts` differs from that of the manifest,
+	/// or it is a custom strategy not set in `opts`.
+	///
+	/// **N.B.** [`opts.entries(/* entries */)`] *and* [`opts.heads(/* heads */)`] *have no effect in the log created.*
+	///
+	/// [`json`]: #method.json
+	/// [sorting strategy]: ../sort/enum.SortStrategy.html
+	/// [`opts.entries(/* entries */)`]: ./struct.LogOptions.html#method.entries
+	/// [`opts.heads(/* heads */)`]: ./struct.LogOptions.html#method.heads
+	pub fn from_manifest (store: Rc<dyn Store>, identity: Identity, opts: LogOptions<C>, hash: &str) -> Option<Log<C>> {
+		let bytes = Runtime::new().unwrap().block_on(store.cat(hash)).ok()?;
+		let manifest: Manifest = serde_json::from_slice(&bytes).ok()?;
+		Log::from_parsed_manifest(store,identity,opts,manifest)
+	}
+
+	fn from_parsed_manifest (store: Rc<dyn Store>, identity: Identity, mut opts: LogOptions<C>, manifest: Manifest) -> Option<Log<C>> {
+		opts.sort = Some(Log::sort_strategy_for(opts.sort,manifest.sort.as_ref().map(|x| &x[..]))?);
+		let es = Log::fetch(&store,&manifest.heads,None,&HashSet::new(),&|n| opts.emit(Event::LoadProgress {
+			loaded: n,
+			total_hint: None,
+		}));
+		Some(Log::new(store,identity,opts.id(&manifest.id).entries(&es).heads(&[])))
+	}
+
+	/// Constructs a new partial log with the identity `identity` from an entry with the hash `hash`
+	/// and at most `amount - 1` of its most recent ancestors, using `opts` for constructor options.
+	///
+	/// Use [`missing`] to list the boundary of the history fetched and [`load_more`] to fetch more of it.
+	///
+	/// **N.B.** [`opts.entries(/* entries */)`] *and* [`opts.heads(/* heads */)`] *have no effect in the log created.*
+	///
+	/// [`missing`]: #method.missing
+	/// [`load_more`]: #method.load_more
+	/// [`opts.entries(/* entries */)`]: ./struct.LogOptions.html#method.entries
+	/// [`opts.heads(/* heads */)`]: ./struct.LogOptions.html#method.heads
+	pub fn from_multihash_partial (store: Rc<dyn Store>, identity: Identity, opts: LogOptions<C>,
+	hash: &str, amount: usize) -> Log<C> {
+		let es = Log::fetch(&store,&[hash.to_owned()],Some(amount),&HashSet::new(),&|n| opts.emit(Event::LoadProgress {
+			loaded: n,
+			total_hint: Some(amount),
+		}));
+		Log::new(store,identity,opts.entries(&es).heads(&[]))
+	}
+
+	/// Constructs a new log with the identity `identity` from the heads listed in the [signed manifest] `manifest`,
+	/// using `opts` for constructor options. The identifier of the log is set to that of the manifest.
+	///
+	/// The manifest is verified with `idp` to be published by the identity with the public key `publisher`
+	/// before any entries are fetched. The [sorting strategy] of the log is set to that of the manifest,
+	/// unless set in `opts`. Returns `None` if the manifest does not pass verification,
+	/// the strategy set in `opts` differs from that of the manifest, or it is a custom strategy not set in `opts`.
+	///
+	/// **N.B.** [`opts.entries(/* entries */)`] *and* [`opts.heads(/* heads */)`] *have no effect in the log created.*
+	///
+	/// [signed manifest]: ../manifest/struct.SignedManifest.html
+	/// [sorting strategy]: ../sort/enum.SortStrategy.html
+	/// [`opts.entries(/* entries */)`]: ./struct.LogOptions.html#method.entries
+	/// [`opts.heads(/* heads */)`]: ./struct.LogOptions.html#method.heads
+	pub fn from_signed_manifest<'a> (store: Rc<dyn Store>, identity: Identity, mut opts: LogOptions<'a,C>,
+	manifest: &'a SignedManifest<C>, idp: &dyn Identificator, publisher: &str) -> Option<Log<C>> {
+		if !manifest.verify(idp,publisher) {
+			return None;
+		}
+		opts.sort = Some(Log::sort_strategy_for(opts.sort,manifest.sort())?);
+		let es = Log::fetch(&store,manifest.heads(),None,&HashSet::new(),&|n| opts.emit(Event::LoadProgress {
+			loaded: n,
+			total_hint: None,
+		}));
+		Some(Log::new(store,identity,opts.id(manifest.id()).entries(&es).heads(&[])))
 	}
 
 	/// Appends `data` into the log as a new entry.
 	///
+	/// If the log has an encryption key, `data` is encrypted with the key of the
+	/// most recent key epoch known to the log before the entry is stored,
+	/// so that the entry hash covers the ciphertext. The ciphertext is bound to the log identifier
+	/// and the clock of the entry, so it cannot be replayed in another entry.
+	/// Use [`payload`] to read the decrypted payload.
+	///
 	/// Returns a reference to the newly created, appended entry.
-	pub fn append (&mut self, data: &str, n_ptr: Option<usize>) -> &Entry {
-		let mut t_new = self.clock.time();
-		for h in &self.heads {
-			t_new = max(t_new,h.clock().time());
+	///
+	/// [`payload`]: #method.payload
+	pub fn append (&mut self, data: &str, n_ptr: Option<usize>) -> &Entry<C> {
+		let clock = self.next_clock();
+		let data = self.seal(data,&clock);
+		self.append_payload(&data,clock,n_ptr)
+	}
+
+	//seals the payload of an entry with the clock if the log has a key, otherwise escapes it
+	fn seal (&self, data: &str, clock: &C) -> String {
+		match self.key {
+			Some(ref k)	=>	k.seal(data,&self.aad(clock)),
+			None		=>	Sealed::escape(data),
 		}
-		t_new = t_new + 1;
-		self.clock = LamportClock::new(self.clock.id()).set_time(t_new);
+	}
+
+	//the associated data of a payload sealed in an entry with the clock
+	fn aad (&self, clock: &C) -> String {
+		format!("{}:{}",self.id,serde_json::to_string(clock).unwrap())
+	}
+
+	//appends the payload as is with the clock, without encrypting it
+	fn append_payload (&mut self, data: &str, clock: C, n_ptr: Option<usize>) -> &Entry<C> {
+		self.clock = clock;
 
 		let mut heads = Vec::new();
 		for h in &self.heads {
@@ -210,13 +477,13 @@ impl Log {
 		self.heads = Log::dedup(&self.heads);
 		self.heads.append(&mut refs);
 
-		//should be created asynchronically in IPFS
+		//should be created asynchronically in the store
 		let mut entry = Entry::new(self.identity.clone(),&self.id,data,
 		&self.heads.iter().map(|x| EntryOrHash::Hash(x.hash().to_owned())).collect::<Vec<_>>()[..],
 		Some(self.clock.clone()));
-		entry.set_hash(&Runtime::new().unwrap().block_on(Entry::multihash(&self.ipfs,&entry)).unwrap());
+		entry.set_hash(&Runtime::new().unwrap().block_on(Entry::multihash(&self.store,&entry)).unwrap());
 		//should be queried asynchronically
-		if !self.access.can_access(&entry) {
+		if !self.access.can_access(&entry,&self.keys) {
 			panic!("Could not append entry, key \"{}\" is not allowed to write in the log",
 			self.identity.id());
 		}
@@ -224,32 +491,293 @@ impl Log {
 		let eh = entry.hash().to_owned();
 		let rc = Rc::new(entry);
 		self.entries.insert(eh.to_owned(),rc.clone());
+		self.index(&rc);
 		for h in &self.heads {
 			self.nexts.insert(h.hash().to_owned());
 		}
-		self.heads.clear();
-		self.heads.push(rc);
+		let old_heads = self.heads.drain(..).map(|x| x.hash().to_owned()).collect();
+		self.heads.push(rc.clone());
 		self.length += 1;
+		self.store_heads();
+		self.pin_appended(&eh,old_heads);
+		self.emit(Event::Appended(rc));
 
 		&self.entries[&eh]
 	}
 
+	fn next_clock (&self) -> C {
+		let mut t_new = self.clock.clone();
+		for h in &self.heads {
+			t_new.merge(h.clock());
+		}
+		t_new.tick();
+		t_new
+	}
+
+	/// Rotates the key of the log's identity to that of `identity`,
+	/// appending a [key rotation] signed with the keys stored for both identities in `idp`.
+	/// Entries appended after this are written by `identity`.
+	///
+	/// Returns a reference to the appended key rotation entry, or `None` if the keys
+	/// of either identity are not found in `idp` or the key has been rotated to another key
+	/// that takes precedence, see [`KeyRegistry::rotate`].
+	///
+	/// [key rotation]: ../control/struct.KeyRotation.html
+	/// [`KeyRegistry::rotate`]: ../control/struct.KeyRegistry.html#method.rotate
+	pub fn rotate_key (&mut self, idp: &dyn Identificator, identity: Identity) -> Option<&Entry<C>> {
+		let rotation = KeyRotation::new(idp,&self.identity,&identity)?;
+		if !self.keys.rotate(&rotation) {
+			return None;
+		}
+		let hash = self.append_payload(&Control::KeyRotation(rotation).to_payload(),self.next_clock(),None).hash().to_owned();
+		self.set_identity(identity);
+		self.get(&hash).map(|x| &**x)
+	}
+
+	/// Revokes the key `key` after the clock time `time`, appending a [revocation]
+	/// signed with the keys stored for the log's identity in `idp`.
+	/// Entries in the log whose clocks carry `key` after `time` are removed.
+	/// As entries are not signed, this is advisory, see [revocation].
+	///
+	/// The log's identity must either own `key` or have been [rotated] from it.
+	/// A log's identity revoking its own key must revoke it from the clock time
+	/// of the appended revocation entry onwards.
+	///
+	/// Returns a reference to the appended revocation entry, or `None` if the revocation is not allowed.
+	///
+	/// [revocation]: ../control/struct.Revocation.html
+	/// [rotated]: #method.rotate_key
+	pub fn revoke (&mut self, idp: &dyn Identificator, key: &str, time: u64) -> Option<&Entry<C>> {
+		if key == self.identity.pub_key() && time < self.next_clock().time() {
+			return None;
+		}
+		let revocation = Revocation::new(idp,&self.identity,key,time)?;
+		if !self.keys.revoke(&revocation) {
+			return None;
+		}
+		let hash = self.append_payload(&Control::Revocation(revocation).to_payload(),self.next_clock(),None).hash().to_owned();
+		let removed = self.purge_revoked();
+		self.emit_truncated(removed);
+		self.get(&hash).map(|x| &**x)
+	}
+
+	/// Adds the readers with the hex-encoded public keys in `readers` to the current key epoch,
+	/// appending a [key management record] with the current log key wrapped to each of them.
+	/// The added readers can decrypt all the entries of the current key epoch.
+	///
+	/// Returns a reference to the appended key management entry, or `None` if the log
+	/// does not have an encryption key or any of `readers` is not a valid public key.
+	///
+	/// [key management record]: ../control/struct.KeyEpoch.html
+	pub fn add_readers (&mut self, readers: &[&str]) -> Option<&Entry<C>> {
+		let record = KeyEpoch::new(self.key.as_ref()?,readers)?;
+		let hash = self.append_payload(&Control::KeyEpoch(record).to_payload(),self.next_clock(),None).hash().to_owned();
+		self.get(&hash).map(|x| &**x)
+	}
+
+	/// Removes the readers with the hex-encoded public keys in `readers`, starting a new key epoch
+	/// with a new log key and appending a [key management record] with the new key wrapped to
+	/// the remaining readers and the log's identity. The removed readers cannot decrypt the entries
+	/// appended after this.
+	///
+	/// Returns a reference to the appended key management entry, or `None` if the log
+	/// does not have an encryption key or the key could not be wrapped to the remaining readers.
+	///
+	/// [key management record]: ../control/struct.KeyEpoch.html
+	pub fn remove_readers (&mut self, readers: &[&str]) -> Option<&Entry<C>> {
+		self.key.as_ref()?;
+		let mut remaining = self.readers().into_iter().filter(|x| !readers.contains(&&x[..])).collect::<BTreeSet<_>>();
+		remaining.insert(self.identity.pub_key().to_owned());
+		let epoch = self.key_epochs().iter().map(|x| x.epoch()).chain(self.keyring.values().map(|x| x.epoch())).max().unwrap_or(0) + 1;
+		let key = LogKey::generate().set_epoch(epoch);
+		let record = KeyEpoch::new(&key,&remaining.iter().map(|x| &x[..]).collect::<Vec<_>>())?;
+		self.keyring.insert(key.id(),key.clone());
+		self.key = Some(key);
+		let hash = self.append_payload(&Control::KeyEpoch(record).to_payload(),self.next_clock(),None).hash().to_owned();
+		self.get(&hash).map(|x| &**x)
+	}
+
+	/// Returns the hex-encoded public keys of the readers of the most recent key epoch in the log.
+	pub fn readers (&self) -> Vec<String> {
+		let records = self.key_epochs();
+		let latest = records.iter().map(|x| x.epoch()).max();
+		records.iter().filter(|x| Some(x.epoch()) == latest).flat_map(|x| x.readers()).
+		map(|x| x.to_owned()).collect::<BTreeSet<_>>().into_iter().collect()
+	}
+
+	/// Unwraps the log keys wrapped to the log's identity in the [key management records]
+	/// of the log, using the keys stored for the log's identity in `idp`.
+	/// The most recent of the known log keys is used to encrypt the entries appended after this.
+	///
+	/// Call after joining other logs to obtain the keys of new key epochs.
+	///
+	/// Returns the number of new log keys obtained.
+	///
+	/// [key management records]: ../control/struct.KeyEpoch.html
+	pub fn unlock (&mut self, idp: &dyn Identificator) -> usize {
+		let keys = match idp.get(self.identity.id()) {
+			Some(k)	=>	k,
+			None	=>	return 0,
+		};
+		let mut count = 0;
+		for r in self.key_epochs() {
+			if !self.keyring.contains_key(r.key_id()) {
+				if let Some(k) = r.unwrap_key(keys) {
+					if self.keyring.insert(k.id(),k).is_none() {
+						count += 1;
+					}
+				}
+			}
+		}
+		//of several keys of the latest epoch, replicas settle on the same one
+		if let Some(latest) = self.keyring.values().max_by_key(|x| (x.epoch(),x.id())) {
+			if self.key.as_ref().map(|x| (x.epoch(),x.id()) < (latest.epoch(),latest.id())).unwrap_or(true) {
+				self.key = Some(latest.clone());
+			}
+		}
+		count
+	}
+
+	fn key_epochs (&self) -> Vec<KeyEpoch> {
+		self.entries.values().filter_map(|x| Control::from_entry(x)).flat_map(Control::records).filter_map(|x| match x {
+			Control::KeyEpoch(r)	=>	Some(r),
+			_						=>	None,
+		}).collect()
+	}
+
+	/// Appends a [checkpoint] of the state `state`, e.g. a snapshot of the application state
+	/// built from the entries of the log, encrypting it if the log has an encryption key.
+	/// The checkpoint carries the control records of the log, so that they outlive the entries
+	/// [pruned] after it.
+	///
+	/// Returns a reference to the appended checkpoint entry.
+	///
+	/// [checkpoint]: ../control/struct.Checkpoint.html
+	/// [pruned]: #method.prune
+	pub fn checkpoint (&mut self, state: &str) -> &Entry<C> {
+		let clock = self.next_clock();
+		let state = self.seal(state,&clock);
+		let controls = self.values().iter().filter_map(|x| Control::from_entry(x)).collect();
+		self.append_payload(&Control::Checkpoint(Checkpoint::new(&state,controls)).to_payload(),clock,None)
+	}
+
+	/// Returns the latest [checkpoint] entry of the log, or `None` if the log has no checkpoints.
+	///
+	/// [checkpoint]: ../control/struct.Checkpoint.html
+	pub fn latest_checkpoint (&self) -> Option<Rc<Entry<C>>> {
+		//the sorting function alone may put a checkpoint before its ancestors
+		self.values().into_iter().filter(|x| Log::is_checkpoint(x)).last()
+	}
+
+	/// Returns the decrypted state of the latest [checkpoint] of the log.
+	///
+	/// Returns `None` if the log has no checkpoints or the state cannot be decrypted.
+	///
+	/// [checkpoint]: ../control/struct.Checkpoint.html
+	pub fn checkpoint_state (&self) -> Option<String> {
+		let checkpoint = self.latest_checkpoint()?;
+		match Control::from_entry(&checkpoint) {
+			Some(Control::Checkpoint(c))	=>	self.open(c.state(),checkpoint.clock()),
+			_								=>	None,
+		}
+	}
+
+	/// Prunes the entries causally before the latest [checkpoint] of the log,
+	/// releasing the pins the log holds on them in the store of the log.
+	///
+	/// The remaining entries keep pointing to the pruned entries by their hashes, so their hashes are unaffected.
+	/// Pruned entries are neither reported [missing] nor joined back from other logs.
+	/// Logs loaded from the store do not follow checkpoints to their parents, so a log without concurrent
+	/// branches starts from the checkpoint instead of the first entry of the log. Entries before the checkpoint
+	/// are still loaded if reached through a branch concurrent with the checkpoint, until they are pruned.
+	///
+	/// Returns the number of entries pruned.
+	///
+	/// [checkpoint]: ../control/struct.Checkpoint.html
+	/// [missing]: #method.missing
+	pub fn prune (&mut self) -> usize {
+		let checkpoint = match self.latest_checkpoint() {
+			Some(c)	=>	c,
+			None	=>	return 0,
+		};
+		let pruned = self.ancestors(checkpoint.hash()).into_iter().map(|x| x.to_owned()).collect::<Vec<_>>();
+		for h in &pruned {
+			self.entries.remove(h);
+			if self.pinned.remove(h) {
+				self.store.unpin(h);
+			}
+			self.excluded.insert(h.to_owned());
+		}
+		self.length = self.entries.len();
+		self.reindex();
+		self.repin();
+		let count = pruned.len();
+		self.emit_truncated(pruned);
+		count
+	}
+
+	fn is_checkpoint (entry: &Entry<C>) -> bool {
+		match Control::from_entry(entry) {
+			Some(Control::Checkpoint(_))	=>	true,
+			_								=>	false,
+		}
+	}
+
+	//removes the entries of revoked keys, their children keep pointing to them like to pruned entries,
+	//and returns their hashes
+	fn purge_revoked (&mut self) -> Vec<String> {
+		let revoked = self.entries.values().filter(|x| !self.access.can_access(x,&self.keys)).
+		map(|x| x.hash().to_owned()).collect::<Vec<_>>();
+		if revoked.is_empty() {
+			return revoked;
+		}
+		for h in &revoked {
+			self.entries.remove(h);
+			self.excluded.insert(h.to_owned());
+		}
+		self.length = self.entries.len();
+		self.heads = Log::find_heads(&self.entries.values().map(|x| x.clone()).collect::<Vec<_>>());
+		self.reindex();
+		self.repin();
+		revoked
+	}
+
+	fn emit_truncated (&self, removed: Vec<String>) {
+		if !removed.is_empty() {
+			self.emit(Event::Truncated {
+				removed: removed,
+			});
+		}
+	}
+
 	/// Joins the log `other` into this log. `other` is kept intact through and after the process.
 	///
 	/// Optionally truncates the log into `size` after joining.
 	///
-	/// Returns a reference to this log.
-	pub fn join (&mut self, other: &Log, size: Option<usize>) -> Option<&Log> {
-		if self.id != other.id {
+	/// Returns a reference to this log, or `None` if the logs have different
+	/// identifiers or [sorting strategies], if they use an unnamed custom strategy, or if the entries joined from `other`
+	/// have any fatal [problems] in their structure.
+	///
+	/// [sorting strategies]: ../sort/enum.SortStrategy.html
+	/// [problems]: ../validation/enum.Problem.html
+	pub fn join (&mut self, other: &Log<C>, size: Option<usize>) -> Option<&Log<C>> {
+		if self.id != other.id || self.sort != other.sort || self.sort.name().is_empty() {
+			return None;
+		}
+		let mut new_items = other.diff(&self);
+		if !self.malformed(&new_items).is_empty() {
 			return None;
 		}
-		let new_items = other.diff(&self);
 
 		//something about identify provider and verification,
 		//implement later
 		//...
 		//...
 
+		let denied = self.admit(&mut new_items);
+		self.excluded.extend(denied);
+		let mut removed = self.purge_revoked();
+
 		for e in &new_items {
 			if let None = self.get(e.0) {
 				self.length += 1;
@@ -263,19 +791,15 @@ impl Log {
 			self.entries.insert(e.0.to_owned(),e.1.clone());
 		}
 
-		let mut nexts_from_new_items = HashSet::new();
-		new_items.into_iter().map(|x| x.1.next().to_owned()).for_each(|n| n.iter().for_each(|n| {
-			nexts_from_new_items.insert(n.to_owned());
-		}));
-		let all_heads = Log::find_heads(&self.heads.iter().chain(other.heads.iter()).map(|x| x.clone()).collect::<Vec<_>>()[..]);
-		let merged_heads: Vec<Rc<Entry>> = all_heads.into_iter().filter(|x| !nexts_from_new_items.contains(&x.hash().to_owned())).
-		filter(|x| !self.nexts.contains(&x.hash().to_owned())).collect();
-		self.heads = Log::dedup(&merged_heads[..]);
+		//the heads of other may have been left out, leaving their parents as heads
+		self.heads = Log::find_heads(&self.entries.values().cloned().collect::<Vec<_>>());
 
 		if let Some(n) = size {
 			let mut vs = self.values();
 			vs.reverse();
-			vs = vs.into_iter().take(n).collect();
+			//joined entries truncated right away are neither reported joined nor removed
+			removed.extend(vs.split_off(n.min(vs.len())).into_iter().map(|x| x.hash().to_owned()).
+			filter(|x| !new_items.contains_key(x)));
 
 			self.entries.clear();
 			for v in &vs {
@@ -286,17 +810,90 @@ impl Log {
 			self.length = self.entries.len();
 		}
 
-		let mut t_max = 0;
+		let mut t_max = C::new(self.identity.pub_key());
 		for h in &self.heads {
-			t_max = max(t_max,h.clock().time());
+			t_max.merge(h.clock());
+		}
+		self.clock = t_max;
+		self.reindex();
+		self.store_heads();
+		self.repin();
+		let mut joined = new_items.into_iter().map(|x| x.1).filter(|x| self.has(x.hash())).collect::<Vec<_>>();
+		joined.sort_by(|a,b| (self.fn_sort)(a,b));
+		if !joined.is_empty() {
+			self.emit(Event::Joined {
+				new_entries: joined,
+				new_heads: self.heads.to_owned(),
+			});
 		}
-		self.clock = LamportClock::new(&self.id).set_time(t_max);
+		self.emit_truncated(removed);
 
 		Some(self)
 	}
 
+	//returns the hashes of the entries in `es` having fatal problems in their structure
+	//together with the entries of the log
+	fn malformed (&self, es: &HashMap<String,Rc<Entry<C>>>) -> HashSet<String> {
+		let all = self.entries.values().chain(es.values()).map(|x| x.clone()).collect::<Vec<_>>();
+		validation::validate(&self.id,&all).iter().filter(|p| p.is_fatal()).flat_map(|p| p.hashes()).
+		filter(|&h| es.contains_key(h)).map(|h| h.to_owned()).collect()
+	}
+
+	//leaves in `es` only the entries the log accepts from other replicas, registering their control records,
+	//and returns the hashes of the entries left out for being written with revoked keys
+	fn admit (&mut self, es: &mut HashMap<String,Rc<Entry<C>>>) -> Vec<String> {
+		//entries the clock does not accept are left out along with their descendants,
+		//so that they may still be added once they are accepted
+		let mut rejected = es.iter().filter(|(_,e)| !self.clock.accepts(e.clock())).
+		map(|(h,_)| h.to_owned()).collect::<HashSet<_>>();
+		loop {
+			let count = rejected.len();
+			for (h,e) in es.iter() {
+				if e.next().iter().any(|n| rejected.contains(n)) {
+					rejected.insert(h.to_owned());
+				}
+			}
+			if rejected.len() == count {
+				break;
+			}
+		}
+		es.retain(|h,_| !self.excluded.contains(h) && !rejected.contains(h));
+		self.keys.register(es.values().map(|x| &**x));
+		let denied = es.iter().filter(|(_,e)| !self.access.can_access(e,&self.keys)).
+		map(|(h,_)| h.to_owned()).collect::<Vec<_>>();
+		for h in &denied {
+			es.remove(h);
+		}
+		denied
+	}
+
+	/// Fetches the entries with the hashes `hashes` and their ancestors not in the log
+	/// from the store of the log, and joins them into the log as [`join`] does.
+	///
+	/// Entries that cannot be fetched, e.g. heads announced by a faulty peer, are skipped.
+	///
+	/// Returns the number of entries added to the log, or `None` if the fetched entries are rejected.
+	///
+	/// [`join`]: #method.join
+	pub fn join_heads (&mut self, hashes: &[String]) -> Option<usize> {
+		let known = self.entries.keys().chain(self.excluded.iter()).map(|x| x.to_owned()).collect();
+		let es = Log::fetch(&self.store,hashes,None,&known,&|n| self.emit(Event::LoadProgress {
+			loaded: n,
+			total_hint: None,
+		}));
+		if es.is_empty() {
+			return Some(0);
+		}
+		let id = self.id.to_owned();
+		let other = Log::new(self.store.clone(),self.identity.clone(),
+		LogOptions::default().id(&id).sort(self.sort.clone()).entries(&es));
+		let length = self.length;
+		self.join(&other,None)?;
+		Some(self.length.saturating_sub(length))
+	}
+
 	/// Returns a map of all the entries contained in this log but not in `other`.
-	pub fn diff (&self, other: &Log) -> HashMap<String,Rc<Entry>> {
+	pub fn diff (&self, other: &Log<C>) -> HashMap<String,Rc<Entry<C>>> {
 		let mut stack: Vec<String> = self.heads.iter().map(|x| x.hash().to_owned()).collect();
 		let mut traversed = HashSet::<&str>::new();
 		let mut diff = HashMap::new();
@@ -320,6 +917,529 @@ impl Log {
 		diff
 	}
 
+	/// Returns `true` if the entry with the hash `a` happened before the entry with the hash `b`,
+	/// otherwise returns `false`.
+	///
+	/// Uses the clocks of the entries if they can tell, otherwise looks for `a`
+	/// among the ancestors of `b` in the log.
+	pub fn happened_before (&self, a: &str, b: &str) -> bool {
+		let (a,b) = match (self.get(a),self.get(b)) {
+			(Some(a),Some(b))	=>	(a,b),
+			_					=>	return false,
+		};
+		match a.clock().happened_before(b.clock()) {
+			Some(x)	=>	x,
+			None	=>	self.is_ancestor(a.hash(),b.hash()),
+		}
+	}
+
+	/// Returns `true` if the entries with the hashes `a` and `b` are distinct entries in the log
+	/// and neither of them happened before the other, otherwise returns `false`.
+	pub fn is_concurrent (&self, a: &str, b: &str) -> bool {
+		a != b && self.has(a) && self.has(b) &&
+		!self.happened_before(a,b) && !self.happened_before(b,a)
+	}
+
+	/// Returns `true` if the entry with the hash `a` is an ancestor of the entry with the hash `b`,
+	/// i.e. `b` points to `a` either directly or through other entries in the log.
+	/// Otherwise returns `false`.
+	pub fn is_ancestor (&self, a: &str, b: &str) -> bool {
+		let (ga,gb) = match (self.generations.get(a),self.generations.get(b)) {
+			(Some(&ga),Some(&gb))	=>	(ga,gb),
+			_						=>	return false,
+		};
+		if ga >= gb {
+			return false;
+		}
+		let mut stack = vec![b];
+		let mut traversed = HashSet::new();
+		while let Some(h) = stack.pop() {
+			for n in self.entries[h].next() {
+				if n == a {
+					return true;
+				}
+				//entries of a lower generation cannot descend from `a`
+				if self.generations.get(n).map(|&g| g > ga).unwrap_or(false) && traversed.insert(&n[..]) {
+					stack.push(n);
+				}
+			}
+		}
+		false
+	}
+
+	/// Returns all the ancestors of the entry with the hash `hash` in the log,
+	/// sorted using the sorting function of the log.
+	pub fn causal_past (&self, hash: &str) -> Vec<Rc<Entry<C>>> {
+		let past = self.ancestors(hash);
+		self.sorted(past.into_iter())
+	}
+
+	/// Returns all the entries in the log that have the entry with the hash `hash` as their ancestor,
+	/// sorted using the sorting function of the log.
+	///
+	/// Same as [`descendants`].
+	///
+	/// [`descendants`]: #method.descendants
+	pub fn causal_future (&self, hash: &str) -> Vec<Rc<Entry<C>>> {
+		self.descendants(hash)
+	}
+
+	/// Returns the entries in the log that point directly to the entry with the hash `hash`,
+	/// sorted using the sorting function of the log.
+	pub fn children (&self, hash: &str) -> Vec<Rc<Entry<C>>> {
+		match self.children.get(hash) {
+			Some(cs)	=>	self.sorted(cs.iter().map(|x| &x[..])),
+			None		=>	Vec::new(),
+		}
+	}
+
+	/// Returns all the entries in the log reachable from the entry with the hash `hash`
+	/// by following the children of entries, sorted using the sorting function of the log.
+	pub fn descendants (&self, hash: &str) -> Vec<Rc<Entry<C>>> {
+		let mut descendants = HashSet::new();
+		let mut stack = vec![hash];
+		while let Some(h) = stack.pop() {
+			for c in self.children.get(h).into_iter().flatten() {
+				if descendants.insert(&c[..]) {
+					stack.push(c);
+				}
+			}
+		}
+		self.sorted(descendants.into_iter())
+	}
+
+	/// Returns all the common ancestors of the entries with the hashes `a` and `b`,
+	/// sorted using the sorting function of the log.
+	///
+	/// An entry counts as its own ancestor here, so if `a` is an ancestor of `b`,
+	/// `a` is among their common ancestors.
+	pub fn common_ancestors (&self, a: &str, b: &str) -> Vec<Rc<Entry<C>>> {
+		let common = self.common_ancestor_set(a,b);
+		self.sorted(common.into_iter())
+	}
+
+	/// Returns the lowest common ancestor of the entries with the hashes `a` and `b`,
+	/// i.e. the point where the histories of `a` and `b` fork.
+	///
+	/// Of several lowest common ancestors, returns the greatest one
+	/// according to the sorting function of the log.
+	///
+	/// Returns `None` if `a` and `b` have no common ancestors.
+	pub fn lowest_common_ancestor (&self, a: &str, b: &str) -> Option<Rc<Entry<C>>> {
+		let common = self.common_ancestor_set(a,b);
+		let parents = common.iter().flat_map(|&x| self.entries[x].next().iter().map(|x| &x[..])).collect::<HashSet<_>>();
+		self.sorted(common.into_iter().filter(|x| !parents.contains(x))).pop()
+	}
+
+	fn common_ancestor_set (&self, a: &str, b: &str) -> HashSet<&str> {
+		let mut pa = self.ancestors(a);
+		let mut pb = self.ancestors(b);
+		if let (Some(a),Some(b)) = (self.get(a),self.get(b)) {
+			pa.insert(a.hash());
+			pb.insert(b.hash());
+		}
+		pa.intersection(&pb).map(|&x| x).collect()
+	}
+
+	fn ancestors (&self, hash: &str) -> HashSet<&str> {
+		let mut ancestors = HashSet::new();
+		let mut stack = self.get(hash).into_iter().collect::<Vec<_>>();
+		while let Some(e) = stack.pop() {
+			for n in e.next() {
+				if let Some(p) = self.get(n) {
+					if ancestors.insert(p.hash()) {
+						stack.push(p);
+					}
+				}
+			}
+		}
+		ancestors
+	}
+
+	fn sorted<'a,I> (&self, hashes: I) -> Vec<Rc<Entry<C>>>
+	where I: Iterator<Item = &'a str> {
+		let mut es = hashes.map(|x| self.entries[x].clone()).collect::<Vec<_>>();
+		es.sort_by(|a,b| (self.fn_sort)(a,b));
+		es
+	}
+
+	//rebuilds the indices of the log from its entries
+	fn reindex (&mut self) {
+		let mut generations = HashMap::<&str,u64>::new();
+		let mut visiting = HashSet::new();
+		for h in self.entries.keys() {
+			let mut stack = vec![(&h[..],false)];
+			while let Some((h,expanded)) = stack.pop() {
+				if generations.contains_key(h) {
+					continue;
+				}
+				let e = &self.entries[h];
+				if expanded {
+					let g = e.next().iter().filter_map(|n| generations.get(&n[..])).max().map(|x| x + 1).unwrap_or(0);
+					generations.insert(h,g);
+				}
+				//an entry being visited again before it is indexed implies a cycle
+				else if visiting.insert(h) {
+					stack.push((h,true));
+					for n in e.next() {
+						if self.entries.contains_key(n) && !visiting.contains(&n[..]) {
+							stack.push((n,false));
+						}
+					}
+				}
+			}
+		}
+		self.generations = generations.into_iter().map(|(k,v)| (k.to_owned(),v)).collect();
+
+		let mut children = HashMap::<String,HashSet<String>>::new();
+		for e in self.entries.values() {
+			for n in e.next() {
+				children.entry(n.to_owned()).or_default().insert(e.hash().to_owned());
+			}
+		}
+		self.nexts = children.keys().map(|x| x.to_owned()).collect();
+		self.children = children;
+
+		//the parents of checkpoints are left out on purpose
+		for e in self.entries.values().filter(|x| Log::is_checkpoint(x)) {
+			for n in e.next() {
+				if !self.entries.contains_key(n) {
+					self.excluded.insert(n.to_owned());
+				}
+			}
+		}
+	}
+
+	//adds an entry appended on top of the indexed entries to the indices
+	fn index (&mut self, entry: &Entry<C>) {
+		let g = entry.next().iter().filter_map(|n| self.generations.get(n)).max().map(|x| x + 1).unwrap_or(0);
+		self.generations.insert(entry.hash().to_owned(),g);
+		for n in entry.next() {
+			self.children.entry(n.to_owned()).or_default().insert(entry.hash().to_owned());
+		}
+	}
+
+	/// Returns the hashes of the entries pointed to by the entries of the log
+	/// but not fetched into it, i.e. the boundary of the history held by the log.
+	///
+	/// Entries left out of the log on purpose, such as entries of revoked keys
+	/// and entries before [checkpoints], are not listed.
+	///
+	/// [checkpoints]: #method.prune
+	pub fn missing (&self) -> Vec<String> {
+		let mut missing = self.children.keys().filter(|&x| !self.has(x) && !self.excluded.contains(x)).
+		map(|x| x.to_owned()).collect::<Vec<_>>();
+		missing.sort();
+		missing
+	}
+
+	/// Returns `true` if the entry with the hash `hash` is left out of the log on purpose,
+	/// such as an entry of a revoked key or an entry before a [checkpoint], otherwise returns `false`.
+	///
+	/// [checkpoint]: #method.prune
+	pub fn is_excluded (&self, hash: &str) -> bool {
+		self.excluded.contains(hash)
+	}
+
+	/// Returns `true` if the log holds its full history, i.e. no entries are [missing],
+	/// otherwise returns `false`.
+	///
+	/// [missing]: #method.missing
+	pub fn is_complete (&self) -> bool {
+		self.missing().is_empty()
+	}
+
+	/// Fetches at most `amount` of the [missing] entries and their ancestors into the log,
+	/// most recent first.
+	///
+	/// Fetched entries go through the same checks as entries joined with [`join`].
+	/// Entries that are malformed, belong to another log or are written with revoked keys
+	/// are left out and not reported missing afterwards. Entries whose clocks are not accepted
+	/// are left out along with their descendants, and stay missing.
+	///
+	/// Returns the number of entries added to the log.
+	///
+	/// [missing]: #method.missing
+	/// [`join`]: #method.join
+	pub fn load_more (&mut self, amount: usize) -> usize {
+		let known = self.entries.keys().chain(self.excluded.iter()).map(|x| x.to_owned()).collect();
+		let es = Log::fetch(&self.store,&self.missing(),Some(amount),&known,&|n| self.emit(Event::LoadProgress {
+			loaded: n,
+			total_hint: Some(amount),
+		}));
+		self.load(es)
+	}
+
+	/// Returns an iterator over the entries of the log, most recent first, that fetches
+	/// the [missing] parents of the entries it reaches, at most `budget` entries in total.
+	///
+	/// Fetched parents are added to the log as in [`load_more`], the ones the log does not accept are skipped.
+	///
+	/// ```ignore
+	/// let mut log = Log::from_multihash_partial(store,identity,LogOptions::new(),hash,1);
+	/// let feed = log.history(100).map(|e| e.payload().to_owned()).take(20).collect::<Vec<_>>();
+	/// ```
+	///
+	/// [missing]: #method.missing
+	/// [`load_more`]: #method.load_more
+	pub fn history (&mut self, budget: usize) -> History<C> {
+		let mut stack = self.heads.to_owned();
+		stack.sort_by(|a,b| (self.fn_sort)(a,b));
+		History {
+			traversed: stack.iter().map(|x| x.hash().to_owned()).collect(),
+			stack: stack,
+			listed: HashSet::new(),
+			log: self,
+			budget: budget,
+			loaded: 0,
+		}
+	}
+
+	/// Constructs a new log with the identity `identity` from the [`snapshot`] `snapshot`,
+	/// using `opts` for constructor options, without fetching any entries.
+	/// The identifier of the log is set to that of the snapshot.
+	///
+	/// The clock and the [sorting strategy] of the log are set to those of the snapshot, unless set in `opts`.
+	/// Returns `None` if `snapshot` is not a snapshot of a supported version, it lacks some of its heads,
+	/// the strategy set in `opts` differs from that of the snapshot, or it is a custom strategy not set in `opts`.
+	///
+	/// To catch up with the entries appended after the snapshot, pass the current heads of the log to
+	/// [`join_heads`], which only fetches the entries not in the restored log.
+	///
+	/// **N.B.** [`opts.entries(/* entries */)`] *and* [`opts.heads(/* heads */)`] *have no effect in the log created.*
+	///
+	/// [`snapshot`]: #method.snapshot
+	/// [`join_heads`]: #method.join_heads
+	/// [sorting strategy]: ../sort/enum.SortStrategy.html
+	/// [`opts.entries(/* entries */)`]: ./struct.LogOptions.html#method.entries
+	/// [`opts.heads(/* heads */)`]: ./struct.LogOptions.html#method.heads
+	pub fn from_snapshot (store: Rc<dyn Store>, identity: Identity, mut opts: LogOptions<C>, snapshot: &str) -> Option<Log<C>> {
+		let snapshot: Snapshot<C> = serde_json::from_str(snapshot).ok()?;
+		if snapshot.v != SNAPSHOT_VERSION {
+			return None;
+		}
+		opts.sort = Some(Log::sort_strategy_for(opts.sort,snapshot.sort.as_ref().map(|x| &x[..]))?);
+		if opts.clock.is_none() {
+			opts.clock = snapshot.clock;
+		}
+		let es = snapshot.entries.into_iter().map(|x| Rc::new(x)).collect::<Vec<_>>();
+		let mut hs = Vec::new();
+		for h in &snapshot.heads {
+			hs.push(es.iter().find(|x| x.hash() == h)?.clone());
+		}
+		Some(Log::new(store,identity,opts.id(&snapshot.id).entries(&es).heads(&hs)))
+	}
+
+	/// Constructs a new log with the identity `identity` from the archive read from `reader`,
+	/// i.e. the output of [`export_car`], using `opts` for constructor options.
+	/// Only archives written by [`export_car`] are supported, see there.
+	/// The identifier and the [sorting strategy] of the log are set as in [`from_manifest`].
+	///
+	/// Every block of the archive is verified and added to the store `store` before the log is built,
+	/// so no other store needs to be reachable. The CIDs of the archive address the blocks only within it:
+	/// the blocks are looked up in the store with the hashes the store gives them when they are added,
+	/// which is how entries point to their parents. The archive must therefore be imported into the same kind
+	/// of store it was exported from, e.g. an archive exported from IPFS, which hashes blocks as UnixFS files,
+	/// cannot be imported into a [`MemoryStore`], which hashes their raw data.
+	///
+	/// Returns an error if reading the archive or adding its blocks fails, the archive is malformed,
+	/// holds no manifest, lacks some of its heads or its roots are not the heads of its manifest,
+	/// the strategy cannot be resolved or the entries have any fatal [problems] in their structure.
+	///
+	/// **N.B.** [`opts.entries(/* entries */)`] *and* [`opts.heads(/* heads */)`] *have no effect in the log created.*
+	///
+	/// [`export_car`]: #method.export_car
+	/// [sorting strategy]: ../sort/enum.SortStrategy.html
+	/// [`from_manifest`]: #method.from_manifest
+	/// [`MemoryStore`]: ../store/struct.MemoryStore.html
+	/// [problems]: ../validation/enum.Problem.html
+	/// [`opts.entries(/* entries */)`]: ./struct.LogOptions.html#method.entries
+	/// [`opts.heads(/* heads */)`]: ./struct.LogOptions.html#method.heads
+	pub fn import_car<R: Read> (store: Rc<dyn Store>, identity: Identity, opts: LogOptions<C>, reader: R) -> StdResult<Log<C>,StoreError> {
+		let (roots,blocks) = car::read_car(reader)?;
+		let mut rt = Runtime::new().unwrap();
+		let mut hashes = HashSet::new();
+		let mut root_hashes = HashSet::new();
+		let mut manifest = None;
+		for (cid,data) in blocks {
+			if let Ok(m) = serde_json::from_slice::<Manifest>(&data) {
+				manifest = Some(m);
+			}
+			let hash = rt.block_on(store.add(data))?;
+			if roots.contains(&cid) {
+				root_hashes.insert(hash.to_owned());
+			}
+			hashes.insert(hash);
+		}
+		let manifest = manifest.ok_or(invalid_data("archive holds no manifest"))?;
+		if !manifest.heads.iter().all(|h| hashes.contains(h)) {
+			return Err(invalid_data("archive lacks some of its heads"));
+		}
+		if root_hashes != manifest.heads.iter().map(|x| x.to_owned()).collect() {
+			return Err(invalid_data("roots of the archive are not the heads of its manifest"));
+		}
+		let log = Log::from_parsed_manifest(store,identity,opts,manifest).ok_or(invalid_data("sorting strategy of the archive not set"))?;
+		if log.validate().iter().any(|p| p.is_fatal()) {
+			return Err(invalid_data("archive holds malformed entries"));
+		}
+		Ok(log)
+	}
+
+	//resolves the sorting strategy of a stored log, given the strategy set in the options
+	fn sort_strategy_for (sort: Option<SortStrategy<C>>, name: Option<&str>) -> Option<SortStrategy<C>> {
+		let name = name.unwrap_or("last-write-wins");
+		match sort {
+			Some(s)	=>	Some(s).filter(|s| s.name() == name),
+			None	=>	SortStrategy::from_name(name),
+		}
+	}
+
+	/// Constructs a new log with the identity `identity` from the latest heads recorded
+	/// in the store `store` for the log with the identifier `id`, using `opts` for constructor options.
+	///
+	/// The heads of a log are recorded in its store whenever entries are appended or joined to it,
+	/// stores such as the [file cache] keep them across restarts.
+	///
+	/// Returns `None` if no heads are recorded for the log.
+	///
+	/// **N.B.** [`opts.entries(/* entries */)`] *and* [`opts.heads(/* heads */)`] *have no effect in the log created.*
+	///
+	/// [file cache]: ../cache/struct.FileCache.html
+	/// [`opts.entries(/* entries */)`]: ./struct.LogOptions.html#method.entries
+	/// [`opts.heads(/* heads */)`]: ./struct.LogOptions.html#method.heads
+	pub fn from_cached_heads (store: Rc<dyn Store>, identity: Identity, opts: LogOptions<C>, id: &str) -> Option<Log<C>> {
+		let hs = store.heads(id)?;
+		Some(Log::from_heads(store,identity,opts.id(id),&hs))
+	}
+
+	fn store_heads (&self) {
+		self.store.set_heads(&self.id,&self.heads.iter().map(|x| x.hash().to_owned()).collect::<Vec<_>>());
+	}
+
+	//pins the entries covered by the pinning policy and unpins the ones pinned before but no longer covered
+	fn repin (&mut self) {
+		self.window = match self.pinning {
+			PinningPolicy::Last(n)	=>	{
+				let vs = self.values();
+				vs[vs.len().saturating_sub(n)..].iter().map(|x| x.hash().to_owned()).collect()
+			},
+			_						=>	VecDeque::new(),
+		};
+		let covered = match self.pinning {
+			PinningPolicy::All		=>	self.entries.keys().map(|x| x.to_owned()).collect(),
+			PinningPolicy::Heads	=>	self.heads.iter().map(|x| x.hash().to_owned()).collect(),
+			PinningPolicy::Last(_)	=>	self.window.iter().map(|x| x.to_owned()).collect(),
+			PinningPolicy::None		=>	HashSet::new(),
+		};
+		for h in covered.difference(&self.pinned) {
+			self.store.pin(h);
+		}
+		for h in self.pinned.difference(&covered) {
+			self.store.unpin(h);
+		}
+		self.pinned = covered;
+	}
+
+	//pins an entry appended on top of the heads `old_heads` as repin would, without going through the log
+	fn pin_appended (&mut self, hash: &str, old_heads: Vec<String>) {
+		let uncovered = match self.pinning {
+			PinningPolicy::All		=>	Vec::new(),
+			PinningPolicy::Heads	=>	old_heads,
+			PinningPolicy::Last(n)	=>	{
+				self.window.push_back(hash.to_owned());
+				let excess = self.window.len().saturating_sub(n);
+				self.window.drain(..excess).collect()
+			},
+			PinningPolicy::None		=>	return,
+		};
+		if !uncovered.iter().any(|x| x == hash) && self.pinned.insert(hash.to_owned()) {
+			self.store.pin(hash);
+		}
+		for h in uncovered {
+			if self.pinned.remove(&h) {
+				self.store.unpin(&h);
+			}
+		}
+	}
+
+	/// Returns the [pinning policy] of the log.
+	///
+	/// [pinning policy]: ../store/enum.PinningPolicy.html
+	pub fn pinning (&self) -> PinningPolicy {
+		self.pinning
+	}
+
+	//the fetcher used by all the loaders, see Entry::fetch_entries_until,
+	//reporting the number of entries fetched so far to progress
+	fn fetch (store: &dyn Store, hashes: &[String], amount: Option<usize>, known: &HashSet<String>,
+	progress: &dyn Fn(usize)) -> Vec<Rc<Entry<C>>> {
+		let loaded = Cell::new(0);
+		Entry::fetch_entries_until(store,hashes,amount,known,|e| {
+			loaded.set(loaded.get() + 1);
+			progress(loaded.get());
+			Log::is_checkpoint(e)
+		}).into_iter().map(|x| Rc::new(x)).collect()
+	}
+
+	/// Subscribes `f` to the [events] of the log.
+	///
+	/// Returns the identifier of the subscription, to be passed to [`unsubscribe`].
+	///
+	/// [events]: ../event/enum.Event.html
+	/// [`unsubscribe`]: #method.unsubscribe
+	pub fn subscribe<F> (&mut self, f: F) -> usize
+	where F: 'static + Fn(&Event<C>) {
+		let id = self.next_subscription;
+		self.next_subscription += 1;
+		self.subscribers.push((id,Box::new(f)));
+		id
+	}
+
+	/// Cancels the subscription with the identifier `id`.
+	///
+	/// Returns `true` if the subscription existed, otherwise returns `false`.
+	pub fn unsubscribe (&mut self, id: usize) -> bool {
+		let n = self.subscribers.len();
+		self.subscribers.retain(|x| x.0 != id);
+		self.subscribers.len() < n
+	}
+
+	fn emit (&self, event: Event<C>) {
+		for (_,f) in &self.subscribers {
+			f(&event);
+		}
+	}
+
+	//adds fetched entries to the log, leaving out the ones it does not accept as join does,
+	//malformed entries and entries of revoked keys for good
+	fn load (&mut self, es: Vec<Rc<Entry<C>>>) -> usize {
+		let mut es = es.into_iter().map(|x| (x.hash().to_owned(),x)).collect::<HashMap<_,_>>();
+		for h in self.malformed(&es) {
+			es.remove(&h);
+			self.excluded.insert(h);
+		}
+		let denied = self.admit(&mut es);
+		self.excluded.extend(denied);
+		let count = es.len();
+		self.entries.extend(es);
+		self.length = self.entries.len();
+		let removed = self.purge_revoked();
+		self.reindex();
+		self.repin();
+		self.emit_truncated(removed);
+		count
+	}
+
+	/// Validates the structure of the log.
+	///
+	/// Returns the [problems] found, see [`validation::validate`].
+	///
+	/// [problems]: ../validation/enum.Problem.html
+	/// [`validation::validate`]: ../validation/fn.validate.html
+	pub fn validate (&self) -> Vec<Problem> {
+		validation::validate(&self.id,&self.entries.values().map(|x| x.clone()).collect::<Vec<_>>())
+	}
+
 	/// Returns the identifier of the log.
 	pub fn id (&self) -> &str {
 		&self.id
@@ -332,16 +1452,37 @@ impl Log {
 	}
 
 	/// Returns a pointer to the entry with the hash `hash`.
-	pub fn get (&self, hash: &str) -> Option<&Rc<Entry>> {
+	pub fn get (&self, hash: &str) -> Option<&Rc<Entry<C>>> {
 		self.entries.get(hash)
 	}
 
+	/// Returns the payload of `entry`, decrypted if it was encrypted.
+	///
+	/// Returns `None` if the payload is encrypted and the log does not have the key to decrypt it.
+	pub fn payload (&self, entry: &Entry<C>) -> Option<String> {
+		self.open(entry.payload(),entry.clock())
+	}
+
+	//opens a payload sealed in an entry with the clock, or unescapes a plaintext one
+	fn open (&self, data: &str, clock: &C) -> Option<String> {
+		if let Some(plain) = Sealed::unescape(data) {
+			return Some(plain);
+		}
+		match Sealed::from_payload(data) {
+			//payloads sealed before keys had identifiers are opened with the keys of their epoch
+			Some(s)	=>	self.keyring.get(s.key_id()).into_iter().
+			chain(self.keyring.values().filter(|k| s.key_id().is_empty() && k.epoch() == s.epoch())).
+			find_map(|k| k.open(data,&self.aad(clock))),
+			None	=>	Some(data.to_owned()),
+		}
+	}
+
 	/// Returns the number of entries in the log.
 	pub fn len (&self) -> usize {
 		self.length
 	}
 
-	pub fn find_heads (entries: &[Rc<Entry>]) -> Vec<Rc<Entry>> {
+	pub fn find_heads (entries: &[Rc<Entry<C>>]) -> Vec<Rc<Entry<C>>> {
 		let mut parents = HashMap::<&str,&str>::new();
 		for e in entries {
 			for n in e.next() {
@@ -366,7 +1507,7 @@ impl Log {
 		heads
 	}
 
-	pub fn find_tails (entries: &[Rc<Entry>]) -> Vec<Rc<Entry>> {
+	pub fn find_tails (entries: &[Rc<Entry<C>>]) -> Vec<Rc<Entry<C>>> {
 		let mut no_nexts = Vec::new();
 		let mut reverses = HashMap::new();
 		let mut nexts = HashSet::new();
@@ -381,22 +1522,22 @@ impl Log {
 			}
 			hashes.insert(e.hash());
 		}
-		//correct order?
+		//tails have no parents among the entries,
+		//so ordering them by their clocks cannot break causality
 		let mut tails = Log::dedup(&nexts.iter().filter(|&&x| !hashes.contains(&x[..])).
 		map(|x| reverses[x].clone()).chain(no_nexts.into_iter()).collect::<Vec<_>>()[..]);
 		tails.sort();
 		tails
 	}
 
-	pub fn find_tail_hashes (entries: &[Rc<Entry>]) -> Vec<String> {
+	pub fn find_tail_hashes (entries: &[Rc<Entry<C>>]) -> Vec<String> {
 		let mut hashes: HashSet<&str> = HashSet::new();
 		for e in entries {
 			hashes.insert(e.hash());
 		}
 		let mut ths = Vec::new();
 		for e in entries {
-			for i in e.next().len() - 1..0 {
-				let n = &e.next()[i];
+			for n in e.next().iter().rev() {
 				if !hashes.contains(&n[..]) {
 					ths.push(n.to_owned());
 				}
@@ -406,38 +1547,110 @@ impl Log {
 		ths
 	}
 
-	fn dedup (v: &[Rc<Entry>]) -> Vec<Rc<Entry>> {
+	fn dedup (v: &[Rc<Entry<C>>]) -> Vec<Rc<Entry<C>>> {
 		let mut s = HashSet::new();
 		v.iter().filter(|x| s.insert(x.hash())).map(|x| x.clone()).collect()
 	}
 
 	pub fn set_identity (&mut self, identity: Identity) {
-		let mut t_max = 0;
+		let mut t_max = C::new(identity.pub_key());
 		for h in &self.heads {
-			t_max = max(t_max,h.clock().time());
+			t_max.merge(h.clock());
 		}
-		self.clock = LamportClock::new(identity.pub_key()).set_time(t_max);
+		self.clock = t_max;
 		self.identity = identity;
 	}
 
-	pub fn clock (&self) -> &LamportClock {
+	pub fn clock (&self) -> &C {
 		&self.clock
 	}
 
-	pub fn values (&self) -> Vec<Rc<Entry>> {
-		let mut es = self.traverse(&self.heads,None,None);
+	/// Returns the sorting strategy of the log.
+	pub fn sort_strategy (&self) -> &SortStrategy<C> {
+		&self.sort
+	}
+
+	pub fn values (&self) -> Vec<Rc<Entry<C>>> {
+		let mut es = self.causal_order();
 		es.reverse();
 		es
 	}
 
-	pub fn heads (&self) -> Vec<Rc<Entry>> {
+	//the entries reachable from the heads, most recent first: of the entries whose children
+	//have all been listed, the greatest one according to the sorting function comes first
+	fn causal_order (&self) -> Vec<Rc<Entry<C>>> {
+		let mut ready = self.heads.to_owned();
+		ready.sort_by(|a,b| (self.fn_sort)(a,b));
+		let mut listed = HashSet::new();
+		let mut order = Vec::with_capacity(self.entries.len());
+		while let Some(e) = ready.pop() {
+			if !listed.insert(e.hash().to_owned()) {
+				continue;
+			}
+			for n in e.next() {
+				if let Some(p) = self.get(n) {
+					if !listed.contains(n) && self.is_unblocked(n,&listed) {
+						let i = match ready.binary_search_by(|x| (self.fn_sort)(x,p)) {
+							Ok(i)	=>	i,
+							Err(i)	=>	i,
+						};
+						ready.insert(i,p.clone());
+					}
+				}
+			}
+			order.push(e);
+		}
+		order
+	}
+
+	//tells whether all the children of the entry with the hash `hash` held by the log are in `listed`
+	fn is_unblocked (&self, hash: &str, listed: &HashSet<String>) -> bool {
+		self.children.get(hash).into_iter().flatten().filter(|c| self.has(c)).all(|c| listed.contains(c))
+	}
+
+	/// Returns the entries of the log in a topological order, i.e. every entry
+	/// comes after all of its parents in the log, regardless of their clocks.
+	///
+	/// Of the entries whose parents have all been listed, the least one according
+	/// to the sorting function of the log comes first, so the order is deterministic.
+	///
+	/// Entries that are their own ancestors are never listed.
+	pub fn topological_order (&self) -> Vec<Rc<Entry<C>>> {
+		let mut pending = self.entries.values().map(|e| {
+			let parents = e.next().iter().filter(|n| self.has(n)).collect::<HashSet<_>>();
+			(e.hash(),parents.len())
+		}).collect::<HashMap<_,_>>();
+		//sorted in descending order, so that the least entry is popped first
+		let mut ready = self.entries.values().filter(|e| pending[e.hash()] == 0).map(|e| e.clone()).collect::<Vec<_>>();
+		ready.sort_by(|a,b| (self.fn_sort)(b,a));
+		let mut order = Vec::with_capacity(self.entries.len());
+		while let Some(e) = ready.pop() {
+			for c in self.children.get(e.hash()).into_iter().flatten() {
+				if let Some(n) = pending.get_mut(&c[..]) {
+					*n -= 1;
+					if *n == 0 {
+						let c = &self.entries[c];
+						let i = match ready.binary_search_by(|x| (self.fn_sort)(c,x)) {
+							Ok(i)	=>	i,
+							Err(i)	=>	i,
+						};
+						ready.insert(i,c.clone());
+					}
+				}
+			}
+			order.push(e);
+		}
+		order
+	}
+
+	pub fn heads (&self) -> Vec<Rc<Entry<C>>> {
 		let mut hs = self.heads.to_owned();
 		hs.sort_by(|a,b| (self.fn_sort)(a,b));
 		hs.reverse();
 		hs
 	}
 
-	pub fn tails (&self) -> Vec<Rc<Entry>> {
+	pub fn tails (&self) -> Vec<Rc<Entry<C>>> {
 		Log::find_tails(&self.values())
 	}
 
@@ -464,10 +1677,8 @@ impl Log {
 		for e in &self.entries {
 			s.push_str(e.0);
 			if !e.1.next().is_empty() {
-				s.push_str("\t\t>");
-				s.push_str(&e.1.next()[0]);
-				s.push_str(", >");
-				s.push_str(&e.1.next()[1]);
+				s.push_str("\t\t");
+				s.push_str(&e.1.next().iter().map(|n| format!(">{}",n)).collect::<Vec<_>>().join(", "));
 			}
 			else {
 				s.push_str("\t\t.,.");
@@ -477,7 +1688,7 @@ impl Log {
 		s
 	}
 
-	pub fn traverse (&self, roots: &[Rc<Entry>], amount: Option<usize>, end_hash: Option<String>) -> Vec<Rc<Entry>> {
+	pub fn traverse (&self, roots: &[Rc<Entry<C>>], amount: Option<usize>, end_hash: Option<String>) -> Vec<Rc<Entry<C>>> {
 		let mut stack = roots.to_owned();
 		stack.sort_by(|a,b| (self.fn_sort)(a,b));
 		stack.reverse();
@@ -518,17 +1729,78 @@ impl Log {
 		json!({
 			"id": self.id,
 			"heads": hs.into_iter().map(|x| x.hash().to_owned()).collect::<Vec<_>>(),
+			"sort": self.sort.name(),
 		}).to_string()
 	}
 
+	/// Stores the manifest of the log, i.e. the output of [`json`], in the store of the log.
+	///
+	/// Returns the multihash of the manifest, from which the log can be loaded with [`from_manifest`].
+	///
+	/// [`json`]: #method.json
+	/// [`from_manifest`]: #method.from_manifest
+	pub fn to_multihash (&self) -> String {
+		Runtime::new().unwrap().block_on(self.store.add(self.json().into_bytes())).unwrap()
+	}
+
+	/// Writes the log to `writer` as an archive holding the blocks of all of its entries
+	/// followed by its manifest, i.e. the output of [`json`], with the heads of the log as its roots.
+	/// The log can be restored from the archive with [`import_car`].
+	///
+	/// The archive is a format of this crate framed as a [CARv1] file, not a CAR export of the log's DAG:
+	/// blocks are addressed with raw SHA-256 CIDs of the data returned by the store, so its roots are not
+	/// the IPFS hashes of the entries and IPFS tooling cannot make use of it, e.g. `ipfs dag import` would
+	/// import the blocks under other hashes than the store gives them. Only [`import_car`] reads it back.
+	///
+	/// Returns an error if a block is not found in the store of the log or writing fails.
+	///
+	/// [CARv1]: https://ipld.io/specs/transport/car/carv1/
+	/// [`json`]: #method.json
+	/// [`import_car`]: #method.import_car
+	pub fn export_car<W: Write> (&self, writer: W) -> StdResult<(),StoreError> {
+		let mut rt = Runtime::new().unwrap();
+		let heads = self.heads.iter().map(|x| x.hash()).collect::<HashSet<_>>();
+		let mut roots = Vec::new();
+		let mut blocks = Vec::new();
+		for e in self.values() {
+			let data = rt.block_on(self.store.cat(e.hash()))?;
+			let cid = car::cid(&data);
+			if heads.contains(e.hash()) {
+				roots.push(cid.clone());
+			}
+			blocks.push((cid,data));
+		}
+		let manifest = self.json().into_bytes();
+		blocks.push((car::cid(&manifest),manifest));
+		car::write_car(writer,&roots,&blocks)?;
+		Ok(())
+	}
+
+	/// Returns a manifest of the log's identifier, heads, sorting strategy and clock,
+	/// signed with the keys stored for the log's identity in `idp`.
+	///
+	/// Returns `None` if the keys of the log's identity are not found in `idp`.
+	pub fn manifest (&self, idp: &dyn Identificator) -> Option<SignedManifest<C>> {
+		let hs = self.heads().into_iter().map(|x| x.hash().to_owned()).collect::<Vec<_>>();
+		SignedManifest::new(idp,&self.identity,&self.id,&hs,Some(&self.sort.name()),&self.clock)
+	}
+
+	/// Returns a snapshot of the log, holding its identifier, the hashes of its heads,
+	/// all of its entries, its clock and the name of its [sorting strategy].
+	///
+	/// The log can be restored from the snapshot with [`from_snapshot`].
+	///
+	/// [sorting strategy]: ../sort/enum.SortStrategy.html
+	/// [`from_snapshot`]: #method.from_snapshot
 	pub fn snapshot (&self) -> String {
-		let hs = self.heads.to_owned();
-		let vs = self.values().to_owned();
-		json!({
-			"id": self.id,
-			"heads": hs.into_iter().map(|x| serde_json::to_string(&*x).unwrap()).collect::<Vec<_>>(),
-			"values": vs.into_iter().map(|x| serde_json::to_string(&*x).unwrap()).collect::<Vec<_>>(),
-		}).to_string()
+		serde_json::to_string(&Snapshot {
+			v: SNAPSHOT_VERSION,
+			id: self.id.to_owned(),
+			heads: self.heads().into_iter().map(|x| x.hash().to_owned()).collect(),
+			entries: self.values().into_iter().map(|x| (*x).clone()).collect(),
+			clock: Some(self.clock.clone()),
+			sort: Some(self.sort.name()),
+		}).unwrap()
 	}
 
 	pub fn buffer (&self) -> Vec<u8> {
@@ -536,34 +1808,130 @@ impl Log {
 	}
 }
 
-impl Display for Log {
+impl<C: Clock> Display for Log<C> {
 	fn fmt (&self, f: &mut Formatter) -> Result {
-		let mut es = self.values();
-		es.reverse();
+		let es = self.values();
+		let positions = es.iter().enumerate().map(|(i,e)| (e.hash(),i)).collect::<HashMap<_,_>>();
 		let mut s = String::new();
-		for e in es {
-			let parents = Entry::find_children(&e,&self.values());
-			if parents.len() >= 1 {
-				if parents.len() >= 2 {
-					for _ in 0..parents.len() - 1 {
+		for e in es.iter().rev() {
+			//the depth of an entry is the length of the chain of its first children
+			let mut depth = 0;
+			let mut current = e.hash();
+			while let Some(c) = self.children.get(current).and_then(|cs| cs.iter().min_by_key(|&c| positions[&c[..]])) {
+				depth += 1;
+				current = c;
+			}
+			if depth >= 1 {
+				if depth >= 2 {
+					for _ in 0..depth - 1 {
 						s.push_str("  ");
 					}
 				}
 				s.push_str("└─");
 			}
-			s.push_str(e.payload());
+			s.push_str(&self.payload(&e).unwrap_or(e.payload().to_owned()));
 			s.push_str("\n");
 		}
 		write!(f,"{}",s)
 	}
 }
 
+fn invalid_data (msg: &str) -> StoreError {
+	StoreError::Io(io::Error::new(io::ErrorKind::InvalidData,msg))
+}
+
+const SNAPSHOT_VERSION: u32 = 1;
+
+//a snapshot of a log, as produced by Log::snapshot
+#[derive(Serialize,Deserialize)]
+struct Snapshot<C> {
+	v: u32,
+	id: String,
+	heads: Vec<String>,
+	entries: Vec<Entry<C>>,
+	clock: Option<C>,
+	sort: Option<String>,
+}
+
+//the manifest of a log, as produced by Log::json
+#[derive(Deserialize)]
+struct Manifest {
+	id: String,
+	heads: Vec<String>,
+	sort: Option<String>,
+}
+
+/// An iterator over the entries of a [log], most recent first, fetching missing history on the way.
+///
+/// Constructed with [`Log::history`].
+///
+/// [log]: ./struct.Log.html
+/// [`Log::history`]: ./struct.Log.html#method.history
+pub struct History<'a,C: Clock = LamportClock> {
+	log: &'a mut Log<C>,
+	stack: Vec<Rc<Entry<C>>>,
+	traversed: HashSet<String>,
+	listed: HashSet<String>,
+	budget: usize,
+	loaded: usize,
+}
+
+impl<'a,C: Clock> History<'a,C> {
+	/// Returns the number of entries the iterator may still fetch.
+	pub fn budget (&self) -> usize {
+		self.budget
+	}
+
+	fn fetch_parents (&mut self, entry: &Entry<C>) {
+		let missing = entry.next().iter().filter(|&x| !self.log.has(x) && !self.log.excluded.contains(x)).
+		take(self.budget).collect::<Vec<_>>();
+		if missing.is_empty() {
+			return;
+		}
+		self.budget -= missing.len();
+		let store = self.log.store.clone();
+		let es = missing.into_iter().filter_map(|h| Runtime::new().unwrap().block_on(Entry::from_multihash(&store,h)).ok()).
+		map(|x| Rc::new(x)).collect::<Vec<_>>();
+		self.loaded += es.len();
+		self.log.emit(Event::LoadProgress {
+			loaded: self.loaded,
+			total_hint: Some(self.loaded + self.budget),
+		});
+		self.log.load(es);
+	}
+}
+
+impl<'a,C: Clock> Iterator for History<'a,C> {
+	type Item = Rc<Entry<C>>;
+
+	fn next (&mut self) -> Option<Self::Item> {
+		let e = self.stack.pop()?;
+		self.listed.insert(e.hash().to_owned());
+		self.fetch_parents(&e);
+		for n in e.next() {
+			if let Some(p) = self.log.get(n) {
+				//an entry comes after all of its children
+				if self.log.is_unblocked(n,&self.listed) && self.traversed.insert(n.to_owned()) {
+					//the stack is kept sorted, the greatest entry on top
+					let i = match self.stack.binary_search_by(|x| (self.log.fn_sort)(x,p)) {
+						Ok(i)	=>	i,
+						Err(i)	=>	i,
+					};
+					self.stack.insert(i,p.clone());
+				}
+			}
+		}
+		Some(e)
+	}
+}
+
 #[doc(hidden)]
 #[derive(Copy,Clone)]
 pub struct AdHocAccess;
 
 impl AdHocAccess {
-	fn can_access (&self, _entry: &Entry) -> bool {
-		true
+	//advisory, as the key in the clock of an entry is not signed
+	fn can_access<C: Clock> (&self, entry: &Entry<C>, keys: &KeyRegistry) -> bool {
+		!keys.is_revoked(entry.clock().id(),entry.clock().time())
 	}
 }
@@ -0,0 +1,110 @@
+use serde::{Serialize,Deserialize};
+use serde_json::json;
+
+use crate::identity::{Identity,Identificator};
+use crate::clock::Clock;
+use crate::lamport_clock::LamportClock;
+
+/// A tamper-evident snapshot of the heads of a [log], signed by the identity publishing it.
+///
+/// Holds the version of the manifest format, the log identifier, the hashes of the heads,
+/// the name of the [sorting strategy] and the clock of the log and the identity of the publisher,
+/// together with the publisher's signature over them.
+///
+/// Anyone can sign a manifest for any log, so a manifest is only as trustworthy
+/// as its publisher, see [`verify`].
+///
+/// [log]: ../log/struct.Log.html
+/// [sorting strategy]: ../sort/enum.SortStrategy.html
+/// [`verify`]: #method.verify
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub struct SignedManifest<C = LamportClock> {
+	v: u32,
+	id: String,
+	heads: Vec<String>,
+	sort: Option<String>,
+	clock: C,
+	publisher: Identity,
+	signature: String,
+}
+
+const MANIFEST_VERSION: u32 = 1;
+
+impl<C: Clock> SignedManifest<C> {
+	/// Constructs a manifest of the log with the identifier `id`, the head hashes `heads`,
+	/// the name of the sorting strategy `sort` and the clock `clock`,
+	/// signing it with the keys stored for `publisher` in `idp`.
+	///
+	/// Returns `None` if the keys of `publisher` are not found in `idp`.
+	pub fn new (idp: &dyn Identificator, publisher: &Identity, id: &str,
+	heads: &[String], sort: Option<&str>, clock: &C) -> Option<SignedManifest<C>> {
+		let name = sort.map(|x| x.to_owned());
+		let signature = idp.sign(&SignedManifest::message(MANIFEST_VERSION,id,heads,&name,clock),idp.get(publisher.id())?);
+		Some(SignedManifest {
+			v: MANIFEST_VERSION,
+			id: id.to_owned(),
+			heads: heads.to_owned(),
+			sort: name,
+			clock: clock.clone(),
+			publisher: publisher.clone(),
+			signature: signature,
+		})
+	}
+
+	fn message (v: u32, id: &str, heads: &[String], sort: &Option<String>, clock: &C) -> String {
+		json!({
+			"v": v,
+			"id": id,
+			"heads": heads,
+			"sort": sort,
+			"clock": clock,
+		}).to_string()
+	}
+
+	/// Parses a manifest from its JSON representation.
+	///
+	/// Returns `None` if `json` is not a manifest. The parsed manifest is **not** verified.
+	pub fn from_json (json: &str) -> Option<SignedManifest<C>> {
+		serde_json::from_str(json).ok()
+	}
+
+	/// Returns the JSON representation of the manifest.
+	pub fn json (&self) -> String {
+		serde_json::to_string(self).unwrap()
+	}
+
+	/// Returns the identifier of the log.
+	pub fn id (&self) -> &str {
+		&self.id
+	}
+
+	/// Returns the hashes of the heads of the log.
+	pub fn heads (&self) -> &[String] {
+		&self.heads
+	}
+
+	/// Returns the name of the sorting strategy of the log, if any.
+	pub fn sort (&self) -> Option<&str> {
+		self.sort.as_ref().map(|x| &x[..])
+	}
+
+	/// Returns the clock of the log.
+	pub fn clock (&self) -> &C {
+		&self.clock
+	}
+
+	/// Returns the identity of the publisher.
+	pub fn publisher (&self) -> &Identity {
+		&self.publisher
+	}
+
+	/// Returns `true` if the manifest is of a supported version, its publisher has the public key `publisher`,
+	/// the publisher's identity is valid and the manifest was signed by the publisher, otherwise returns `false`.
+	///
+	/// `publisher` is the key of the identity trusted to publish the log, known beforehand.
+	pub fn verify (&self, idp: &dyn Identificator, publisher: &str) -> bool {
+		self.v == MANIFEST_VERSION && self.publisher.pub_key() == publisher &&
+		idp.verify_identity(&self.publisher) &&
+		idp.verify(&SignedManifest::message(self.v,&self.id,&self.heads,&self.sort,&self.clock),&self.signature,publisher)
+	}
+}
@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use serde::{Serialize,Deserialize,Serializer,Deserializer};
+use serde::de::Error;
+use sha2::{Sha256,Digest};
+use hex;
+
+use crate::clock::Clock;
+use crate::log::Log;
+
+//bits per element and hash functions for a false positive rate of about 1%
+const BITS_PER_ELEMENT: usize = 10;
+const HASH_FUNCTIONS: u32 = 7;
+
+/// A [Bloom filter] of entry hashes.
+///
+/// Tells for certain that a hash is not in the filter, but may tell that a hash
+/// is in the filter when it is not, with a probability of about 1%.
+///
+/// [Bloom filter]: https://en.wikipedia.org/wiki/Bloom_filter
+#[derive(Clone,Debug,Eq,PartialEq,Serialize,Deserialize)]
+pub struct BloomFilter {
+	#[serde(serialize_with = "to_hex",deserialize_with = "from_hex")]
+	bits: Vec<u8>,
+	hashes: u32,
+}
+
+impl BloomFilter {
+	/// Constructs a filter of the hashes `hashes`.
+	pub fn new<'a,I> (hashes: I) -> BloomFilter
+	where I: IntoIterator<Item = &'a str> {
+		let hashes = hashes.into_iter().collect::<Vec<_>>();
+		let mut filter = BloomFilter {
+			bits: vec![0; (hashes.len()*BITS_PER_ELEMENT + 7)/8 + 1],
+			hashes: HASH_FUNCTIONS,
+		};
+		for h in hashes {
+			for i in filter.indices(h) {
+				filter.bits[i/8] |= 1 << (i % 8);
+			}
+		}
+		filter
+	}
+
+	/// Returns `true` if `hash` may be in the filter, `false` if it certainly is not.
+	pub fn contains (&self, hash: &str) -> bool {
+		!self.bits.is_empty() && self.indices(hash).into_iter().all(|i| self.bits[i/8] & 1 << (i % 8) != 0)
+	}
+
+	//double hashing over the SHA-256 digest of the hash
+	fn indices (&self, hash: &str) -> Vec<usize> {
+		let d = Sha256::digest(hash.as_bytes());
+		let h1 = d[..8].iter().fold(0u64,|n,&x| n << 8 | x as u64);
+		let h2 = d[8..16].iter().fold(0u64,|n,&x| n << 8 | x as u64) | 1;
+		let m = (self.bits.len()*8) as u64;
+		(0..self.hashes as u64).map(|i| (h1.wrapping_add(i.wrapping_mul(h2)) % m) as usize).collect()
+	}
+}
+
+fn to_hex<S: Serializer> (bits: &[u8], s: S) -> Result<S::Ok,S::Error> {
+	s.serialize_str(&hex::encode(bits))
+}
+
+fn from_hex<'de,D: Deserializer<'de>> (d: D) -> Result<Vec<u8>,D::Error> {
+	hex::decode(String::deserialize(d)?).map_err(D::Error::custom)
+}
+
+/// An entry offered to a peer, as its hash and the hashes of its parents.
+#[derive(Clone,Debug,Eq,PartialEq,Serialize,Deserialize)]
+pub struct Offered {
+	hash: String,
+	next: Vec<String>,
+}
+
+impl Offered {
+	/// Returns the hash of the entry.
+	pub fn hash (&self) -> &str {
+		&self.hash
+	}
+
+	/// Returns the hashes of the parents of the entry.
+	pub fn next (&self) -> &[String] {
+		&self.next
+	}
+}
+
+/// A message of the reconciliation protocol, see [`Reconciler`].
+///
+/// [`Reconciler`]: ./struct.Reconciler.html
+#[derive(Clone,Debug,Eq,PartialEq,Serialize,Deserialize)]
+#[serde(tag = "type",rename_all = "snake_case")]
+pub enum Message {
+	/// Opens a reconciliation with the heads and the frontier of the sender.
+	///
+	/// The frontier is a sample of the history of the sender, entries further and further
+	/// from its heads, so that the receiver finds common entries even if it knows none of the heads.
+	Heads {
+		heads: Vec<String>,
+		frontier: Vec<String>,
+	},
+	/// The heads and the frontier of the sender and a filter of its entries not in the causal past
+	/// of the receiver's heads and frontier, i.e. the entries the receiver may be missing.
+	Sketch {
+		heads: Vec<String>,
+		frontier: Vec<String>,
+		filter: BloomFilter,
+	},
+	/// Entries the sender has and the receiver is missing, parents first.
+	Offer {
+		entries: Vec<Offered>,
+	},
+	/// Hashes of entries the receiver was not offered but is missing,
+	/// due to false positives in its filter.
+	Want {
+		hashes: Vec<String>,
+	},
+}
+
+/// One side of a reconciliation between replicas of a [log], computing the entries
+/// that the local log is missing from the log of a peer.
+///
+/// The peers first exchange their heads, along with a frontier of entries sampled at exponentially
+/// growing distances from their heads. Each peer then sends a [Bloom filter] of its entries not
+/// in the causal past of the heads and the frontier of the other peer it knows, so the filters
+/// and the entries offered after them grow with the difference between the logs
+/// rather than with the logs themselves. Heads and parents of offered entries that were withheld
+/// due to false positives in the filters are asked for separately.
+///
+/// One peer opens the reconciliation with [`start`], after which both pass
+/// the messages they receive to [`handle`] and send the messages it returns,
+/// until [`is_done`] holds. The hashes of the missing entries can then be fetched
+/// from a store and joined, see [`missing`].
+///
+/// ```ignore
+/// let (mut a,mut b) = (Reconciler::new(),Reconciler::new());
+/// let mut to_b = vec![a.start(&log_a)];
+/// while !to_b.is_empty() {
+///     let to_a = to_b.drain(..).flat_map(|m| b.handle(&log_b,m)).collect::<Vec<_>>();
+///     to_b = to_a.into_iter().flat_map(|m| a.handle(&log_a,m)).collect();
+/// }
+/// ```
+///
+/// [log]: ../log/struct.Log.html
+/// [Bloom filter]: ./struct.BloomFilter.html
+/// [`start`]: #method.start
+/// [`handle`]: #method.handle
+/// [`is_done`]: #method.is_done
+/// [`missing`]: #method.missing
+#[derive(Default)]
+pub struct Reconciler {
+	sent_sketch: bool,
+	sent_offer: bool,
+	received_offer: bool,
+	offered: HashMap<String,Vec<String>>,
+	order: Vec<String>,
+	peer_heads: Vec<String>,
+	wanted: HashSet<String>,
+	unavailable: HashSet<String>,
+}
+
+impl Reconciler {
+	/// Constructs a new reconciler.
+	pub fn new () -> Reconciler {
+		Reconciler::default()
+	}
+
+	/// Opens a reconciliation of `log` with a peer.
+	///
+	/// Returns the message to send to the peer.
+	pub fn start<C: Clock> (&mut self, log: &Log<C>) -> Message {
+		Message::Heads {
+			heads: Reconciler::heads(log),
+			frontier: Reconciler::frontier(log),
+		}
+	}
+
+	/// Handles the message `msg` received from the peer, reconciling `log` with the log of the peer.
+	///
+	/// Returns the messages to send to the peer, in order.
+	pub fn handle<C: Clock> (&mut self, log: &Log<C>, msg: Message) -> Vec<Message> {
+		match msg {
+			Message::Heads {heads,frontier}			=>	{
+				self.sent_sketch = true;
+				self.peer_heads = heads;
+				vec![Reconciler::sketch(log,&self.peer_heads,&frontier)]
+			},
+			Message::Sketch {heads,frontier,filter}	=>	{
+				let mut msgs = Vec::new();
+				if !self.sent_sketch {
+					self.sent_sketch = true;
+					msgs.push(Reconciler::sketch(log,&heads,&frontier));
+				}
+				self.sent_offer = true;
+				msgs.push(Message::Offer {
+					entries: Reconciler::candidates(log,&heads,&frontier).into_iter().
+					filter(|x| !filter.contains(&x.hash)).collect(),
+				});
+				self.peer_heads = heads;
+				msgs
+			},
+			Message::Offer {entries}				=>	{
+				self.received_offer = true;
+				self.receive(log,entries)
+			},
+			Message::Want {hashes}					=>	vec![Message::Offer {
+				entries: hashes.iter().filter_map(|h| log.get(h)).map(|e| Offered {
+					hash: e.hash().to_owned(),
+					next: e.next().to_owned(),
+				}).collect(),
+			}],
+		}
+	}
+
+	/// Returns `true` if the reconciliation is complete on this side, otherwise returns `false`.
+	pub fn is_done (&self) -> bool {
+		self.sent_offer && self.received_offer && self.wanted.is_empty()
+	}
+
+	/// Returns the hashes of the entries the local log is missing from the log of the peer, parents first.
+	pub fn missing (&self) -> Vec<String> {
+		//entries asked for separately come after their children in the order of offering
+		let mut missing = Vec::new();
+		let mut done = HashSet::new();
+		for h in &self.order {
+			let mut stack = vec![(&h[..],false)];
+			while let Some((h,expanded)) = stack.pop() {
+				if expanded {
+					missing.push(h.to_owned());
+				}
+				else if done.insert(h) {
+					stack.push((h,true));
+					for n in self.offered[h].iter().rev().filter(|&n| self.offered.contains_key(n)) {
+						stack.push((n,false));
+					}
+				}
+			}
+		}
+		missing
+	}
+
+	fn receive<C: Clock> (&mut self, log: &Log<C>, entries: Vec<Offered>) -> Vec<Message> {
+		for e in entries {
+			self.wanted.remove(&e.hash);
+			if !log.has(&e.hash) && !self.offered.contains_key(&e.hash) {
+				self.order.push(e.hash.to_owned());
+				self.offered.insert(e.hash,e.next);
+			}
+		}
+		//wanted entries not offered in return are not available from the peer
+		self.unavailable.extend(self.wanted.drain());
+
+		//heads of the peer withheld due to false positives have no offered children pointing to them
+		let mut gaps = self.offered.values().flat_map(|x| x.iter()).chain(self.peer_heads.iter()).
+		filter(|&n| !log.has(n) && !log.is_excluded(n) && !self.offered.contains_key(n) && !self.unavailable.contains(n)).
+		map(|x| x.to_owned()).collect::<Vec<_>>();
+		gaps.sort();
+		gaps.dedup();
+		if gaps.is_empty() {
+			return Vec::new();
+		}
+		self.wanted = gaps.iter().map(|x| x.to_owned()).collect();
+		vec![Message::Want {
+			hashes: gaps,
+		}]
+	}
+
+	fn heads<C: Clock> (log: &Log<C>) -> Vec<String> {
+		log.heads().iter().map(|x| x.hash().to_owned()).collect()
+	}
+
+	//the entries after the heads in the topological order, most recent first, at the positions 2^k - 1
+	fn frontier<C: Clock> (log: &Log<C>) -> Vec<String> {
+		let heads = Reconciler::heads(log);
+		log.topological_order().into_iter().rev().filter(|x| !heads.iter().any(|h| h == x.hash())).
+		enumerate().filter(|(i,_)| (i + 1).is_power_of_two()).map(|(_,x)| x.hash().to_owned()).collect()
+	}
+
+	fn sketch<C: Clock> (log: &Log<C>, peer_heads: &[String], peer_frontier: &[String]) -> Message {
+		let candidates = Reconciler::candidates(log,peer_heads,peer_frontier);
+		Message::Sketch {
+			heads: Reconciler::heads(log),
+			frontier: Reconciler::frontier(log),
+			filter: BloomFilter::new(candidates.iter().map(|x| &x.hash[..])),
+		}
+	}
+
+	//the entries of the log not in the causal past of the heads and the frontier of the peer known to it, parents first
+	fn candidates<C: Clock> (log: &Log<C>, peer_heads: &[String], peer_frontier: &[String]) -> Vec<Offered> {
+		let mut covered = HashSet::new();
+		for h in peer_heads.iter().chain(peer_frontier.iter()).filter(|&x| log.has(x)) {
+			covered.insert(h.to_owned());
+			covered.extend(log.causal_past(h).iter().map(|x| x.hash().to_owned()));
+		}
+		log.topological_order().into_iter().filter(|x| !covered.contains(x.hash())).map(|e| Offered {
+			hash: e.hash().to_owned(),
+			next: e.next().to_owned(),
+		}).collect()
+	}
+}
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::io;
+use serde::Deserialize;
+
+use crate::clock::Clock;
+use crate::log::Log;
+use crate::transport::Transport;
+
+/// A head announcement exchanged by replicators, i.e. the output of [`Log::json`].
+///
+/// [`Log::json`]: ../log/struct.Log.html#method.json
+#[derive(Clone,Debug,Eq,PartialEq,Deserialize)]
+pub struct Announcement {
+	id: String,
+	heads: Vec<String>,
+	sort: Option<String>,
+}
+
+impl Announcement {
+	/// Parses an announcement from `data`.
+	///
+	/// Returns `None` if `data` is not an announcement.
+	pub fn from_bytes (data: &[u8]) -> Option<Announcement> {
+		serde_json::from_slice(data).ok()
+	}
+
+	/// Returns the identifier of the announced log.
+	pub fn id (&self) -> &str {
+		&self.id
+	}
+
+	/// Returns the hashes of the announced heads.
+	pub fn heads (&self) -> &[String] {
+		&self.heads
+	}
+}
+
+/// Replicates a [log] with peers over a [transport], by exchanging head announcements.
+///
+/// Announcements received from a peer are queued per sender as the transport names it,
+/// which need not be the address the peer was added with, and the entries they point to
+/// are fetched from the store of the log, verified and joined into it (see [`Log::join_heads`]).
+/// The log is announced to all peers whenever entries are joined into it, and on [`announce`].
+///
+/// Queues are bounded both ways. As announcements of a log supersede the earlier ones,
+/// the oldest message queued for or from a peer is dropped when its queue is full.
+/// Messages the transport cannot take yet are kept in the queue of the peer and sent again on [`poll`],
+/// and at most one announcement per peer is processed on each poll.
+///
+/// ```ignore
+/// let mut replicator = Replicator::new(TcpTransport::bind("127.0.0.1:0").unwrap(),16);
+/// replicator.add_peer("127.0.0.1:4001");
+/// replicator.announce(&log);
+/// loop {
+///     replicator.poll(&mut log).unwrap();
+/// }
+/// ```
+///
+/// [log]: ../log/struct.Log.html
+/// [transport]: ../transport/trait.Transport.html
+/// [`Log::join_heads`]: ../log/struct.Log.html#method.join_heads
+/// [`announce`]: #method.announce
+/// [`poll`]: #method.poll
+pub struct Replicator<T> {
+	transport: T,
+	limit: usize,
+	peers: Vec<String>,
+	outgoing: HashMap<String,VecDeque<Vec<u8>>>,
+	incoming: HashMap<String,VecDeque<Announcement>>,
+}
+
+impl<T: Transport> Replicator<T> {
+	/// Constructs a new replicator over the transport `transport`,
+	/// queuing at most `limit` messages for and from each peer.
+	pub fn new (transport: T, limit: usize) -> Replicator<T> {
+		Replicator {
+			transport: transport,
+			limit: limit.max(1),
+			peers: Vec::new(),
+			outgoing: HashMap::new(),
+			incoming: HashMap::new(),
+		}
+	}
+
+	/// Returns the transport of the replicator.
+	pub fn transport (&self) -> &T {
+		&self.transport
+	}
+
+	/// Adds the peer with the address `peer`, to which the log is announced.
+	///
+	/// Announcements are received from any peer, added or not.
+	pub fn add_peer (&mut self, peer: &str) {
+		if !self.peers.iter().any(|x| x == peer) {
+			self.peers.push(peer.to_owned());
+		}
+	}
+
+	/// Returns the addresses of the peers added.
+	pub fn peers (&self) -> &[String] {
+		&self.peers
+	}
+
+	/// Returns the number of messages queued for the peer with the address `peer`.
+	pub fn pending (&self, peer: &str) -> usize {
+		self.outgoing.get(peer).map(|x| x.len()).unwrap_or(0)
+	}
+
+	/// Announces the heads of `log` to all peers.
+	pub fn announce<C: Clock> (&mut self, log: &Log<C>) {
+		let data = log.json().into_bytes();
+		for p in &self.peers {
+			let queue = self.outgoing.entry(p.to_owned()).or_default();
+			if queue.len() >= self.limit {
+				queue.pop_front();
+			}
+			queue.push_back(data.clone());
+		}
+		self.flush();
+	}
+
+	/// Sends the queued messages, receives the announcements of peers, and joins
+	/// the entries announced into `log`, processing at most one announcement per peer.
+	///
+	/// Announcements of other logs or of logs using another [sorting strategy] are ignored.
+	///
+	/// Returns the number of entries joined into `log`, or an error if receiving fails.
+	///
+	/// [sorting strategy]: ../sort/enum.SortStrategy.html
+	pub fn poll<C: Clock> (&mut self, log: &mut Log<C>) -> io::Result<usize> {
+		self.flush();
+		while let Some((peer,data)) = self.transport.recv()? {
+			let a = match Announcement::from_bytes(&data) {
+				Some(a)	=>	a,
+				None	=>	continue,
+			};
+			if a.id != log.id() || a.sort.as_ref().map(|x| &x[..]).unwrap_or("last-write-wins") != log.sort_strategy().name() {
+				continue;
+			}
+			let queue = self.incoming.entry(peer).or_default();
+			if queue.len() >= self.limit {
+				queue.pop_front();
+			}
+			queue.push_back(a);
+		}
+
+		let mut joined = 0;
+		let mut peers = self.incoming.keys().map(|x| x.to_owned()).collect::<Vec<_>>();
+		peers.sort();
+		for p in peers {
+			let a = match self.incoming.get_mut(&p).and_then(|x| x.pop_front()) {
+				Some(a)	=>	a,
+				None	=>	continue,
+			};
+			//senders come and go, e.g. with every connection
+			if self.incoming[&p].is_empty() {
+				self.incoming.remove(&p);
+			}
+			let unknown = a.heads.into_iter().filter(|h| !log.has(h) && !log.is_excluded(h)).collect::<Vec<_>>();
+			if !unknown.is_empty() {
+				joined += log.join_heads(&unknown).unwrap_or(0);
+			}
+		}
+		if joined > 0 {
+			self.announce(log);
+		}
+		Ok(joined)
+	}
+
+	//sends the queued messages until the transport cannot take any more for a peer
+	fn flush (&mut self) {
+		for (p,queue) in self.outgoing.iter_mut() {
+			while let Some(data) = queue.front() {
+				match self.transport.send(p,data) {
+					Ok(())	=>	{
+						queue.pop_front();
+					},
+					Err(ref e) if e.kind() == io::ErrorKind::WouldBlock	=>	break,
+					//an unreachable peer is tried again on the next poll
+					Err(_)	=>	break,
+				}
+			}
+		}
+	}
+}
@@ -0,0 +1,147 @@
+use std::cmp::Ordering;
+use std::fmt::{Debug,Formatter,Result};
+use std::rc::Rc;
+
+use crate::entry::Entry;
+use crate::clock::Clock;
+use crate::lamport_clock::LamportClock;
+
+/// A named strategy for sorting the entries of a [log].
+///
+/// The name of the strategy is stored in the manifest of the log (see [`Log::json`]),
+/// so that replicas can tell whether they order their entries in the same way.
+/// Logs using different strategies are never joined.
+///
+/// | strategy | name |
+/// |----------|------|
+/// | [`LastWriteWins`] | `last-write-wins` |
+/// | [`EntryHash`] | `entry-hash` |
+/// | [`ClockIdPriority`] | `clock-id-priority` |
+/// | [`WriterPriority`] | `writer-priority:` followed by the comma-separated writers |
+/// | [`Custom`] | the name given to [`custom`] |
+///
+/// [log]: ../log/struct.Log.html
+/// [`Log::json`]: ../log/struct.Log.html#method.json
+/// [`LastWriteWins`]: #variant.LastWriteWins
+/// [`EntryHash`]: #variant.EntryHash
+/// [`ClockIdPriority`]: #variant.ClockIdPriority
+/// [`WriterPriority`]: #variant.WriterPriority
+/// [`Custom`]: #variant.Custom
+/// [`custom`]: #method.custom
+pub enum SortStrategy<C = LamportClock> {
+	/// Sorts with [`Entry::last_write_wins`].
+	///
+	/// [`Entry::last_write_wins`]: ../entry/struct.Entry.html#method.last_write_wins
+	LastWriteWins,
+	/// Sorts with [`Entry::sort_by_entry_hash`].
+	///
+	/// [`Entry::sort_by_entry_hash`]: ../entry/struct.Entry.html#method.sort_by_entry_hash
+	EntryHash,
+	/// Sorts by the scalar times of the clocks, resolving equal times
+	/// by the clock identifiers and then by the entry hashes.
+	ClockIdPriority,
+	/// Sorts with [`Entry::sort_by_writer_priority`], using the listed
+	/// clock identifiers of writers, highest priority first.
+	///
+	/// Of concurrent entries, the ones of writers with higher priorities come later
+	/// whatever their clock times, so that their writes take precedence on conflicts.
+	///
+	/// [`Entry::sort_by_writer_priority`]: ../entry/struct.Entry.html#method.sort_by_writer_priority
+	WriterPriority(Vec<String>),
+	/// Sorts with a custom sorting function registered under a name.
+	///
+	/// The strategy is unnamed if the name is empty, as set by [`LogOptions::fn_sort`].
+	///
+	/// [`LogOptions::fn_sort`]: ../log/struct.LogOptions.html#method.fn_sort
+	Custom(String,Rc<dyn Fn(&Entry<C>,&Entry<C>) -> Ordering>),
+}
+
+impl<C: Clock> SortStrategy<C> {
+	/// Registers the sorting function `fn_sort` as a custom strategy under the name `name`.
+	///
+	/// Replicas of a log must register the same function under the same name.
+	///
+	/// Returns `None` if `name` is empty or the name of a predefined strategy.
+	pub fn custom<F> (name: &str, fn_sort: F) -> Option<SortStrategy<C>>
+	where F: 'static + Fn(&Entry<C>,&Entry<C>) -> Ordering {
+		if name.is_empty() || SortStrategy::<C>::from_name(name).is_some() {
+			return None;
+		}
+		Some(SortStrategy::Custom(name.to_owned(),Rc::new(fn_sort)))
+	}
+
+	/// Returns the predefined strategy with the name `name`.
+	///
+	/// Returns `None` if there is no predefined strategy with the name,
+	/// custom strategies cannot be constructed from their names.
+	pub fn from_name (name: &str) -> Option<SortStrategy<C>> {
+		if name.starts_with("writer-priority:") {
+			let writers = &name["writer-priority:".len()..];
+			return Some(SortStrategy::WriterPriority(if writers.is_empty() {
+				Vec::new()
+			}
+			else {
+				writers.split(',').map(|x| x.to_owned()).collect()
+			}));
+		}
+		match name {
+			"last-write-wins"	=>	Some(SortStrategy::LastWriteWins),
+			"entry-hash"		=>	Some(SortStrategy::EntryHash),
+			"clock-id-priority"	=>	Some(SortStrategy::ClockIdPriority),
+			_					=>	None,
+		}
+	}
+
+	/// Returns the name of the strategy.
+	pub fn name (&self) -> String {
+		match self {
+			SortStrategy::LastWriteWins			=>	"last-write-wins".to_owned(),
+			SortStrategy::EntryHash				=>	"entry-hash".to_owned(),
+			SortStrategy::ClockIdPriority		=>	"clock-id-priority".to_owned(),
+			SortStrategy::WriterPriority(ws)	=>	format!("writer-priority:{}",ws.join(",")),
+			SortStrategy::Custom(name,_)		=>	name.to_owned(),
+		}
+	}
+
+	/// Returns the sorting function of the strategy.
+	pub fn function (&self) -> Box<dyn Fn(&Entry<C>,&Entry<C>) -> Ordering> {
+		match self {
+			SortStrategy::LastWriteWins			=>	Box::new(Entry::last_write_wins),
+			SortStrategy::EntryHash				=>	Box::new(Entry::sort_by_entry_hash),
+			SortStrategy::ClockIdPriority		=>	Box::new(|a: &Entry<C>,b: &Entry<C>| {
+				a.clock().time().cmp(&b.clock().time()).
+				then_with(|| a.clock().id().cmp(b.clock().id())).
+				then_with(|| a.hash().cmp(b.hash()))
+			}),
+			SortStrategy::WriterPriority(ws)	=>	Box::new(Entry::sort_by_writer_priority(ws.to_owned())),
+			SortStrategy::Custom(_,f)			=>	{
+				let f = f.clone();
+				Box::new(move |a,b| f(a,b))
+			},
+		}
+	}
+}
+
+impl<C> Clone for SortStrategy<C> {
+	fn clone (&self) -> Self {
+		match self {
+			SortStrategy::LastWriteWins			=>	SortStrategy::LastWriteWins,
+			SortStrategy::EntryHash				=>	SortStrategy::EntryHash,
+			SortStrategy::ClockIdPriority		=>	SortStrategy::ClockIdPriority,
+			SortStrategy::WriterPriority(ws)	=>	SortStrategy::WriterPriority(ws.to_owned()),
+			SortStrategy::Custom(name,f)		=>	SortStrategy::Custom(name.to_owned(),f.clone()),
+		}
+	}
+}
+
+impl<C: Clock> Debug for SortStrategy<C> {
+	fn fmt (&self, f: &mut Formatter) -> Result {
+		write!(f,"SortStrategy({})",self.name())
+	}
+}
+
+impl<C: Clock> PartialEq for SortStrategy<C> {
+	fn eq (&self, other: &Self) -> bool {
+		self.name() == other.name()
+	}
+}
@@ -0,0 +1,251 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error;
+use std::fmt::{Display,Formatter,Result};
+use std::io;
+use std::io::Cursor;
+use std::rc::Rc;
+use futures::{Future,Stream};
+use futures::future;
+use ipfs_api::IpfsClient;
+use tokio::runtime::Runtime;
+use sha2::{Sha256,Digest};
+use hex;
+
+/// A future returned by a [store].
+///
+/// [store]: ./trait.Store.html
+pub type StoreFuture<T> = Box<dyn Future<Item = T,Error = StoreError> + Send>;
+
+/// An error returned by a [store].
+///
+/// [store]: ./trait.Store.html
+#[derive(Debug)]
+pub enum StoreError {
+	/// No block with the hash is found in the store.
+	NotFound(String),
+	/// Reading or writing a local file failed.
+	Io(io::Error),
+	/// The remote block store returned an error.
+	Remote(String),
+	/// The block is not of the expected format, e.g. not an entry.
+	Invalid(String),
+}
+
+impl Display for StoreError {
+	fn fmt (&self, f: &mut Formatter) -> Result {
+		match self {
+			StoreError::NotFound(h)	=>	write!(f,"block {} not found",h),
+			StoreError::Io(e)		=>	write!(f,"{}",e),
+			StoreError::Remote(e)	=>	write!(f,"{}",e),
+			StoreError::Invalid(e)	=>	write!(f,"{}",e),
+		}
+	}
+}
+
+impl error::Error for StoreError {}
+
+impl From<io::Error> for StoreError {
+	fn from (e: io::Error) -> StoreError {
+		StoreError::Io(e)
+	}
+}
+
+/// A content-addressed block store holding the entries and manifests of [logs].
+///
+/// Implemented for [`IpfsClient`], for a [`MemoryStore`] and for a [`FileCache`]
+/// in front of any other store.
+///
+/// [logs]: ../log/struct.Log.html
+/// [`IpfsClient`]: https://docs.rs/ipfs-api
+/// [`MemoryStore`]: ./struct.MemoryStore.html
+/// [`FileCache`]: ../cache/struct.FileCache.html
+pub trait Store {
+	/// Stores `data` and returns a future containing its hash.
+	fn add (&self, data: Vec<u8>) -> StoreFuture<String>;
+
+	/// Returns a future containing the data stored with the hash `hash`.
+	fn cat (&self, hash: &str) -> StoreFuture<Vec<u8>>;
+
+	/// Returns the latest heads recorded for the log with the identifier `log_id`.
+	///
+	/// Returns `None` by default.
+	fn heads (&self, _log_id: &str) -> Option<Vec<String>> {
+		None
+	}
+
+	/// Records `heads` as the latest heads of the log with the identifier `log_id`.
+	///
+	/// Does nothing by default.
+	fn set_heads (&self, _log_id: &str, _heads: &[String]) {}
+
+	/// Keeps the block with the hash `hash` from being removed from the store.
+	///
+	/// Stores shared by several logs should count the pins of a block, keeping it pinned
+	/// until each of them is released with [`unpin`], as logs only release their own pins.
+	///
+	/// Does nothing by default.
+	///
+	/// [`unpin`]: #method.unpin
+	fn pin (&self, _hash: &str) {}
+
+	/// Releases a pin of the block with the hash `hash`, e.g. after it is pruned from a log,
+	/// allowing the block to be removed from the store once it has no pins left.
+	///
+	/// Does nothing by default.
+	fn unpin (&self, _hash: &str) {}
+}
+
+/// A policy for pinning the entries of a [log] in its [store].
+///
+/// The policy is applied whenever entries are appended, joined, loaded, truncated or pruned:
+/// entries it covers are pinned and entries it no longer covers, or that are [pruned], are unpinned.
+/// A log only releases the pins it holds itself, so logs sharing a store do not unpin each other's entries
+/// as long as the store counts pins.
+///
+/// [log]: ../log/struct.Log.html
+/// [store]: ./trait.Store.html
+/// [pruned]: ../log/struct.Log.html#method.prune
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum PinningPolicy {
+	/// Pins all the entries of the log.
+	All,
+	/// Pins the heads of the log.
+	Heads,
+	/// Pins the given number of the most recent entries of the log.
+	Last(usize),
+	/// Pins no entries.
+	None,
+}
+
+impl Default for PinningPolicy {
+	fn default () -> Self {
+		PinningPolicy::None
+	}
+}
+
+impl Store for IpfsClient {
+	fn add (&self, data: Vec<u8>) -> StoreFuture<String> {
+		Box::new(IpfsClient::add(self,Cursor::new(data)).map(|x| x.hash).
+		map_err(|e| StoreError::Remote(e.to_string())))
+	}
+
+	fn cat (&self, hash: &str) -> StoreFuture<Vec<u8>> {
+		Box::new(IpfsClient::cat(self,hash).concat2().map(|x| x.to_vec()).
+		map_err(|e| StoreError::Remote(e.to_string())))
+	}
+
+	//pins of an IPFS node are not counted, logs sharing a node unpin each other's entries
+	fn pin (&self, hash: &str) {
+		//pinning is best effort, an unpinned block is only at risk of garbage collection
+		let _ = Runtime::new().unwrap().block_on(self.pin_add(hash,true));
+	}
+
+	fn unpin (&self, hash: &str) {
+		let _ = Runtime::new().unwrap().block_on(self.pin_rm(hash,true));
+	}
+}
+
+impl<S: Store + ?Sized> Store for Rc<S> {
+	fn add (&self, data: Vec<u8>) -> StoreFuture<String> {
+		(**self).add(data)
+	}
+
+	fn cat (&self, hash: &str) -> StoreFuture<Vec<u8>> {
+		(**self).cat(hash)
+	}
+
+	fn heads (&self, log_id: &str) -> Option<Vec<String>> {
+		(**self).heads(log_id)
+	}
+
+	fn set_heads (&self, log_id: &str, heads: &[String]) {
+		(**self).set_heads(log_id,heads)
+	}
+
+	fn pin (&self, hash: &str) {
+		(**self).pin(hash)
+	}
+
+	fn unpin (&self, hash: &str) {
+		(**self).unpin(hash)
+	}
+}
+
+/// A block store held in memory, addressing blocks with their hex-encoded SHA-256 digests.
+///
+/// Blocks can be pinned, and unpinned blocks are removed with [`gc`] like in an IPFS node.
+/// Pins are counted, a block stays pinned until each of its pins is released.
+///
+/// Useful for tests and for logs that are never shared.
+///
+/// [`gc`]: #method.gc
+#[derive(Default)]
+pub struct MemoryStore {
+	blocks: RefCell<HashMap<String,Vec<u8>>>,
+	heads: RefCell<HashMap<String,Vec<String>>>,
+	pins: RefCell<HashMap<String,usize>>,
+}
+
+impl MemoryStore {
+	/// Constructs a new, empty memory store.
+	pub fn new () -> MemoryStore {
+		MemoryStore::default()
+	}
+
+	/// Returns the number of blocks in the store.
+	pub fn len (&self) -> usize {
+		self.blocks.borrow().len()
+	}
+
+	/// Returns `true` if the block with the hash `hash` is pinned, otherwise returns `false`.
+	pub fn is_pinned (&self, hash: &str) -> bool {
+		self.pins.borrow().contains_key(hash)
+	}
+
+	/// Removes all the blocks that are not pinned.
+	///
+	/// Returns the number of blocks removed.
+	pub fn gc (&self) -> usize {
+		let pins = self.pins.borrow();
+		let mut blocks = self.blocks.borrow_mut();
+		let n = blocks.len();
+		blocks.retain(|h,_| pins.contains_key(h));
+		n - blocks.len()
+	}
+}
+
+impl Store for MemoryStore {
+	fn add (&self, data: Vec<u8>) -> StoreFuture<String> {
+		let hash = hex::encode(Sha256::digest(&data));
+		self.blocks.borrow_mut().insert(hash.to_owned(),data);
+		Box::new(future::ok(hash))
+	}
+
+	fn cat (&self, hash: &str) -> StoreFuture<Vec<u8>> {
+		Box::new(future::result(self.blocks.borrow().get(hash).map(|x| x.to_owned()).
+		ok_or(StoreError::NotFound(hash.to_owned()))))
+	}
+
+	fn heads (&self, log_id: &str) -> Option<Vec<String>> {
+		self.heads.borrow().get(log_id).map(|x| x.to_owned())
+	}
+
+	fn set_heads (&self, log_id: &str, heads: &[String]) {
+		self.heads.borrow_mut().insert(log_id.to_owned(),heads.to_owned());
+	}
+
+	fn pin (&self, hash: &str) {
+		*self.pins.borrow_mut().entry(hash.to_owned()).or_insert(0) += 1;
+	}
+
+	fn unpin (&self, hash: &str) {
+		let mut pins = self.pins.borrow_mut();
+		if let Some(n) = pins.get_mut(hash) {
+			*n -= 1;
+			if *n == 0 {
+				pins.remove(hash);
+			}
+		}
+	}
+}
@@ -0,0 +1,340 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::io;
+use std::io::{Read,Write};
+use std::net::{TcpListener,TcpStream,ToSocketAddrs};
+use std::rc::Rc;
+use std::sync::mpsc::{channel,Receiver,TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+/// A transport carrying messages between the peers of a [replicator].
+///
+/// Peers are named by their addresses on the transport. Both sending and receiving
+/// must not block: a transport that cannot take a message for a peer right now
+/// fails with [`WouldBlock`], and the message is kept to be sent again later.
+///
+/// Implemented for a [`MemoryTransport`] within a process and for a [`TcpTransport`].
+///
+/// [replicator]: ../replicator/struct.Replicator.html
+/// [`WouldBlock`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock
+/// [`MemoryTransport`]: ./struct.MemoryTransport.html
+/// [`TcpTransport`]: ./struct.TcpTransport.html
+pub trait Transport {
+	/// Returns the address of the local peer.
+	fn address (&self) -> &str;
+
+	/// Sends the message `data` to the peer with the address `peer`.
+	fn send (&mut self, peer: &str, data: &[u8]) -> io::Result<()>;
+
+	/// Returns the next message received, with the address of the peer that sent it
+	/// as known to the transport, or `None` if no message has been received.
+	///
+	/// The address identifies the sender, but need not be one messages can be sent to.
+	fn recv (&mut self) -> io::Result<Option<(String,Vec<u8>)>>;
+}
+
+//the messages received by a peer, with the addresses of their senders
+type Inbox = VecDeque<(String,Vec<u8>)>;
+
+/// A network of [`MemoryTransport`]s within a process, delivering messages in order.
+///
+/// Every peer has an inbox holding at most a given number of messages,
+/// messages sent to a peer with a full inbox fail with [`WouldBlock`].
+///
+/// ```ignore
+/// let network = MemoryNetwork::new(16);
+/// let (a,b) = (network.connect("a"),network.connect("b"));
+/// ```
+///
+/// [`MemoryTransport`]: ./struct.MemoryTransport.html
+/// [`WouldBlock`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock
+#[derive(Clone)]
+pub struct MemoryNetwork {
+	inboxes: Rc<RefCell<HashMap<String,Inbox>>>,
+	capacity: usize,
+}
+
+impl MemoryNetwork {
+	/// Constructs a new network, with inboxes holding at most `capacity` messages.
+	pub fn new (capacity: usize) -> MemoryNetwork {
+		MemoryNetwork {
+			inboxes: Rc::new(RefCell::new(HashMap::new())),
+			capacity: capacity,
+		}
+	}
+
+	/// Connects a peer with the address `address` to the network.
+	pub fn connect (&self, address: &str) -> MemoryTransport {
+		self.inboxes.borrow_mut().entry(address.to_owned()).or_default();
+		MemoryTransport {
+			network: self.clone(),
+			address: address.to_owned(),
+		}
+	}
+}
+
+/// A transport connected to a [`MemoryNetwork`].
+///
+/// [`MemoryNetwork`]: ./struct.MemoryNetwork.html
+pub struct MemoryTransport {
+	network: MemoryNetwork,
+	address: String,
+}
+
+impl Transport for MemoryTransport {
+	fn address (&self) -> &str {
+		&self.address
+	}
+
+	fn send (&mut self, peer: &str, data: &[u8]) -> io::Result<()> {
+		let mut inboxes = self.network.inboxes.borrow_mut();
+		let inbox = inboxes.get_mut(peer).
+		ok_or(io::Error::new(io::ErrorKind::NotFound,format!("peer {} not connected",peer)))?;
+		if inbox.len() >= self.network.capacity {
+			return Err(io::Error::new(io::ErrorKind::WouldBlock,format!("inbox of peer {} is full",peer)));
+		}
+		inbox.push_back((self.address.to_owned(),data.to_owned()));
+		Ok(())
+	}
+
+	fn recv (&mut self) -> io::Result<Option<(String,Vec<u8>)>> {
+		Ok(self.network.inboxes.borrow_mut().get_mut(&self.address).and_then(|x| x.pop_front()))
+	}
+}
+
+/// A transport over TCP, addressing peers with the addresses of their listeners.
+///
+/// Messages are framed with their length, and frames longer than [`MAX_FRAME`] are neither
+/// sent nor received: a peer sending one is disconnected. Connections to peers are opened
+/// in the background on the first message sent and kept open. Each connection buffers
+/// at most one message not yet written, further messages fail with [`WouldBlock`]
+/// until it is written, on the next send or receive.
+///
+/// Messages received are attributed to the remote address of the connection they arrive on,
+/// so that a peer cannot pose as another one. That address is not the address of the listener
+/// of the peer, so replies cannot be sent to it, and a peer that reconnects is seen as a new one.
+/// At most [`MAX_CONNECTIONS`] connections are accepted at a time, further ones are closed right away.
+///
+/// ```ignore
+/// let mut a = TcpTransport::bind("127.0.0.1:0").unwrap();
+/// let b = TcpTransport::bind("127.0.0.1:0").unwrap();
+/// a.send(b.address(),b"hello").unwrap();
+/// ```
+///
+/// [`MAX_FRAME`]: ./constant.MAX_FRAME.html
+/// [`MAX_CONNECTIONS`]: ./constant.MAX_CONNECTIONS.html
+/// [`WouldBlock`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock
+pub struct TcpTransport {
+	address: String,
+	listener: TcpListener,
+	outgoing: HashMap<String,(Connection,Vec<u8>)>,
+	incoming: Vec<(TcpStream,String,Vec<u8>)>,
+	received: Inbox,
+}
+
+/// The maximum length of a message sent over a [`TcpTransport`], in bytes.
+///
+/// [`TcpTransport`]: ./struct.TcpTransport.html
+pub const MAX_FRAME: usize = 1 << 24;
+
+/// The maximum number of connections a [`TcpTransport`] accepts at a time.
+///
+/// [`TcpTransport`]: ./struct.TcpTransport.html
+pub const MAX_CONNECTIONS: usize = 64;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+//an outgoing connection, opened by a thread of its own so that sending does not block
+enum Connection {
+	Connecting(Receiver<io::Result<TcpStream>>),
+	Open(TcpStream),
+}
+
+impl Connection {
+	fn open (peer: &str) -> Connection {
+		let (tx,rx) = channel();
+		let peer = peer.to_owned();
+		thread::spawn(move || {
+			let stream = peer.to_socket_addrs().and_then(|mut x| x.next().
+			ok_or(io::Error::new(io::ErrorKind::InvalidInput,format!("peer {} has no address",peer)))).
+			and_then(|x| TcpStream::connect_timeout(&x,CONNECT_TIMEOUT)).
+			and_then(|x| x.set_nonblocking(true).map(|_| x));
+			let _ = tx.send(stream);
+		});
+		Connection::Connecting(rx)
+	}
+
+	//writes as much of `buf` as the connection takes
+	fn write (&mut self, buf: &mut Vec<u8>) -> io::Result<()> {
+		if let Connection::Connecting(rx) = self {
+			match rx.try_recv() {
+				Ok(stream)						=>	*self = Connection::Open(stream?),
+				Err(TryRecvError::Empty)		=>	return if buf.is_empty() {
+					Ok(())
+				}
+				else {
+					Err(io::Error::new(io::ErrorKind::WouldBlock,"connection is being opened"))
+				},
+				Err(TryRecvError::Disconnected)	=>	return Err(io::Error::new(io::ErrorKind::NotConnected,"connection failed")),
+			}
+		}
+		if let Connection::Open(stream) = self {
+			while !buf.is_empty() {
+				match stream.write(buf) {
+					Ok(0)	=>	return Err(io::Error::new(io::ErrorKind::WriteZero,"connection closed")),
+					Ok(n)	=>	{
+						buf.drain(..n);
+					},
+					Err(e)	=>	return Err(e),
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+impl TcpTransport {
+	/// Binds a listener to the address `address` and constructs a transport receiving on it.
+	pub fn bind<A: ToSocketAddrs> (address: A) -> io::Result<TcpTransport> {
+		let listener = TcpListener::bind(address)?;
+		listener.set_nonblocking(true)?;
+		Ok(TcpTransport {
+			address: listener.local_addr()?.to_string(),
+			listener: listener,
+			outgoing: HashMap::new(),
+			incoming: Vec::new(),
+			received: VecDeque::new(),
+		})
+	}
+
+	//accepts new connections and reads the frames received on all of them
+	fn receive (&mut self) -> io::Result<()> {
+		loop {
+			match self.listener.accept() {
+				//dropping the stream closes the connection
+				Ok(_) if self.incoming.len() >= MAX_CONNECTIONS	=>	(),
+				Ok((stream,address))	=>	{
+					stream.set_nonblocking(true)?;
+					self.incoming.push((stream,address.to_string(),Vec::new()));
+				},
+				Err(ref e) if e.kind() == io::ErrorKind::WouldBlock	=>	break,
+				Err(e)					=>	return Err(e),
+			}
+		}
+		let mut closed = Vec::new();
+		for (i,(stream,address,buf)) in self.incoming.iter_mut().enumerate() {
+			let mut chunk = [0; 4096];
+			loop {
+				match stream.read(&mut chunk) {
+					Ok(0)	=>	{
+						closed.push(i);
+						break;
+					},
+					Ok(n)	=>	buf.extend_from_slice(&chunk[..n]),
+					Err(ref e) if e.kind() == io::ErrorKind::WouldBlock	=>	break,
+					Err(_)	=>	{
+						closed.push(i);
+						break;
+					},
+				}
+				//reads no more than a frame ahead
+				if buf.len() > MAX_FRAME + 4 {
+					break;
+				}
+			}
+			loop {
+				match TcpTransport::frame(buf) {
+					Some(Ok((data,len)))	=>	{
+						self.received.push_back((address.to_owned(),data));
+						buf.drain(..len);
+					},
+					Some(Err(()))			=>	{
+						if closed.last() != Some(&i) {
+							closed.push(i);
+						}
+						break;
+					},
+					None					=>	break,
+				}
+			}
+		}
+		for i in closed.into_iter().rev() {
+			self.incoming.remove(i);
+		}
+		Ok(())
+	}
+
+	//writes as much of the buffered frames as the connections take, dropping the ones that fail
+	fn flush (&mut self) {
+		let failed = self.outgoing.iter_mut().filter_map(|(p,(connection,buf))| match connection.write(buf) {
+			Err(ref e) if e.kind() != io::ErrorKind::WouldBlock	=>	Some(p.to_owned()),
+			_														=>	None,
+		}).collect::<Vec<_>>();
+		for p in failed {
+			self.outgoing.remove(&p);
+		}
+	}
+
+	//a frame is the length of the data and the data, or an error if the length is over the maximum
+	fn frame (buf: &[u8]) -> Option<Result<(Vec<u8>,usize),()>> {
+		if buf.len() < 4 {
+			return None;
+		}
+		let len = u32::from_be_bytes([buf[0],buf[1],buf[2],buf[3]]) as usize;
+		if len > MAX_FRAME {
+			return Some(Err(()));
+		}
+		if buf.len() < len + 4 {
+			return None;
+		}
+		Some(Ok((buf[4..len + 4].to_vec(),len + 4)))
+	}
+}
+
+impl Transport for TcpTransport {
+	fn address (&self) -> &str {
+		&self.address
+	}
+
+	fn send (&mut self, peer: &str, data: &[u8]) -> io::Result<()> {
+		if data.len() > MAX_FRAME {
+			return Err(io::Error::new(io::ErrorKind::InvalidInput,format!("message of {} bytes is too long",data.len())));
+		}
+		if !self.outgoing.contains_key(peer) {
+			self.outgoing.insert(peer.to_owned(),(Connection::open(peer),Vec::new()));
+		}
+		let (connection,buf) = self.outgoing.get_mut(peer).unwrap();
+		//a connection still being opened takes no data yet
+		let result = match connection.write(buf) {
+			Ok(())	=>	{
+				buf.extend_from_slice(&(data.len() as u32).to_be_bytes());
+				buf.extend_from_slice(data);
+				match connection.write(buf) {
+					Err(ref e) if e.kind() == io::ErrorKind::WouldBlock	=>	Ok(()),
+					result	=>	result,
+				}
+			},
+			Err(ref e) if e.kind() == io::ErrorKind::WouldBlock	=>	{
+				Err(io::Error::new(io::ErrorKind::WouldBlock,format!("connection to peer {} is busy",peer)))
+			},
+			Err(e)	=>	Err(e),
+		};
+		if let Err(ref e) = result {
+			if e.kind() != io::ErrorKind::WouldBlock {
+				//reconnect on the next message
+				self.outgoing.remove(peer);
+			}
+		}
+		result
+	}
+
+	fn recv (&mut self) -> io::Result<Option<(String,Vec<u8>)>> {
+		self.flush();
+		if self.received.is_empty() {
+			self.receive()?;
+		}
+		Ok(self.received.pop_front())
+	}
+}
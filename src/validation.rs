@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use crate::entry::Entry;
+use crate::clock::Clock;
+
+/// A structural problem found among the entries of a [log].
+///
+/// All problems but [`MissingParent`] and [`DuplicateNext`] are fatal: entries with them are never joined into a log.
+/// Missing parents are expected in logs that do not hold their full history, and entries created by earlier
+/// versions list their only parent twice.
+///
+/// [log]: ../log/struct.Log.html
+/// [`MissingParent`]: #variant.MissingParent
+/// [`DuplicateNext`]: #variant.DuplicateNext
+#[derive(Clone,Debug,Eq,PartialEq)]
+pub enum Problem {
+	/// The entry with the hash points to itself.
+	SelfReference(String),
+	/// The entries with the hashes form a cycle, each entry pointing to the one after it
+	/// and the last entry pointing to the first one.
+	Cycle(Vec<String>),
+	/// The entry with the first hash points to the second hash more than once.
+	DuplicateNext(String,String),
+	/// The entry with the hash belongs to a log with a different identifier.
+	IdMismatch(String),
+	/// The entry with the first hash points to the second hash, which is not among the entries.
+	MissingParent(String,String),
+}
+
+impl Problem {
+	/// Returns `true` if the problem is fatal, otherwise returns `false`.
+	pub fn is_fatal (&self) -> bool {
+		match self {
+			Problem::MissingParent(_,_)	=>	false,
+			Problem::DuplicateNext(_,_)	=>	false,
+			_							=>	true,
+		}
+	}
+
+	/// Returns the hashes of the entries having the problem.
+	pub fn hashes (&self) -> Vec<&str> {
+		match self {
+			Problem::SelfReference(h)		=>	vec![h],
+			Problem::Cycle(hs)				=>	hs.iter().map(|x| &x[..]).collect(),
+			Problem::DuplicateNext(h,_)		=>	vec![h],
+			Problem::IdMismatch(h)			=>	vec![h],
+			Problem::MissingParent(h,_)		=>	vec![h],
+		}
+	}
+}
+
+/// Validates the structure of `entries` as entries of the log with the identifier `log_id`.
+///
+/// Returns the problems found, per entry in the order of `entries`, followed by the cycles.
+pub fn validate<C: Clock> (log_id: &str, entries: &[Rc<Entry<C>>]) -> Vec<Problem> {
+	let mut problems = Vec::new();
+	let index = entries.iter().map(|e| (e.hash(),e)).collect::<HashMap<_,_>>();
+	for e in entries {
+		let mut seen = HashSet::new();
+		for n in e.next() {
+			if n == e.hash() {
+				problems.push(Problem::SelfReference(e.hash().to_owned()));
+			}
+			else if !seen.insert(n) {
+				problems.push(Problem::DuplicateNext(e.hash().to_owned(),n.to_owned()));
+			}
+			else if !index.contains_key(&n[..]) {
+				problems.push(Problem::MissingParent(e.hash().to_owned(),n.to_owned()));
+			}
+		}
+		if e.id() != log_id {
+			problems.push(Problem::IdMismatch(e.hash().to_owned()));
+		}
+	}
+
+	//depth-first search, a pointer to an entry on the current path closes a cycle
+	let mut done = HashSet::new();
+	for e in entries {
+		if done.contains(e.hash()) {
+			continue;
+		}
+		let mut path = vec![(e.hash(),0)];
+		let mut on_path = vec![e.hash()].into_iter().collect::<HashSet<_>>();
+		while let Some(&mut (h,ref mut i)) = path.last_mut() {
+			let next = index[h].next();
+			if *i == next.len() {
+				path.pop();
+				on_path.remove(h);
+				done.insert(h);
+				continue;
+			}
+			let n = &next[*i][..];
+			*i += 1;
+			if n == h || done.contains(n) || !index.contains_key(n) {
+				continue;
+			}
+			if on_path.contains(n) {
+				let start = path.iter().position(|&(x,_)| x == n).unwrap();
+				problems.push(Problem::Cycle(path[start..].iter().map(|&(x,_)| x.to_owned()).collect()));
+			}
+			else {
+				path.push((n,0));
+				on_path.insert(n);
+			}
+		}
+	}
+	problems
+}
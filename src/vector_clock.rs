@@ -0,0 +1,134 @@
+use serde::{Serialize,Deserialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+use crate::clock::Clock;
+
+/// A [vector clock] for partial chronological ordering of events,
+/// able to tell whether two events are concurrent.
+///
+/// Holds a counter for each clock identifier it has seen. The [`time`] of a vector clock
+/// is the sum of its counters, which together with the identifier forms its total order.
+///
+/// [vector clock]: https://en.wikipedia.org/wiki/Vector_clock
+/// [`time`]: #method.time
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub struct VectorClock {
+	id: String,
+	clocks: BTreeMap<String,u64>,
+}
+
+impl VectorClock {
+	/// Constructs a new vector clock with the given identifier.
+	pub fn new (id: &str) -> VectorClock {
+		VectorClock {
+			id: id.to_owned(),
+			clocks: BTreeMap::new(),
+		}
+	}
+
+	/// Returns the counter of the clock identifier `id`.
+	pub fn get (&self, id: &str) -> u64 {
+		*self.clocks.get(id).unwrap_or(&0)
+	}
+
+	/// Returns the identifier of the vector clock.
+	pub fn id (&self) -> &str {
+		&self.id
+	}
+
+	/// Returns the sum of all the counters of the vector clock.
+	pub fn time (&self) -> u64 {
+		self.clocks.values().sum()
+	}
+
+	/// Advances the counter of the vector clock's own identifier.
+	pub fn tick (&mut self) {
+		*self.clocks.entry(self.id.to_owned()).or_insert(0) += 1;
+	}
+
+	/// Merges `o` to `self` by taking the greater of the two counters
+	/// for each clock identifier. `o` is never modified.
+	pub fn merge (&mut self, o: &VectorClock) {
+		for (k,&v) in &o.clocks {
+			let c = self.clocks.entry(k.to_owned()).or_insert(0);
+			if *c < v {
+				*c = v;
+			}
+		}
+	}
+
+	/// Compares the counters of `self` and `o`.
+	///
+	/// Returns `Some(Ordering::Less)` if `self` happened before `o`, `Some(Ordering::Greater)`
+	/// if `o` happened before `self`, `Some(Ordering::Equal)` if the counters are equal,
+	/// and `None` if `self` and `o` are concurrent.
+	pub fn causal_cmp (&self, o: &VectorClock) -> Option<Ordering> {
+		let (mut less,mut greater) = (false,false);
+		for k in self.clocks.keys().chain(o.clocks.keys()) {
+			match self.get(k).cmp(&o.get(k)) {
+				Ordering::Less		=>	less = true,
+				Ordering::Greater	=>	greater = true,
+				Ordering::Equal		=>	(),
+			}
+		}
+		match (less,greater) {
+			(false,false)	=>	Some(Ordering::Equal),
+			(true,false)	=>	Some(Ordering::Less),
+			(false,true)	=>	Some(Ordering::Greater),
+			(true,true)		=>	None,
+		}
+	}
+}
+
+impl Clock for VectorClock {
+	fn new (id: &str) -> Self {
+		VectorClock::new(id)
+	}
+
+	fn id (&self) -> &str {
+		self.id()
+	}
+
+	fn time (&self) -> u64 {
+		self.time()
+	}
+
+	fn tick (&mut self) {
+		self.tick()
+	}
+
+	fn merge (&mut self, o: &Self) {
+		self.merge(o)
+	}
+
+	fn happened_before (&self, o: &Self) -> Option<bool> {
+		Some(self.causal_cmp(o) == Some(Ordering::Less))
+	}
+}
+
+impl PartialEq for VectorClock {
+	fn eq (&self, other: &Self) -> bool {
+		self.id == other.id && self.causal_cmp(other) == Some(Ordering::Equal)
+	}
+}
+
+impl Eq for VectorClock {}
+
+impl Ord for VectorClock {
+	fn cmp (&self, other: &Self) -> Ordering {
+		self.time().cmp(&other.time()).
+		then_with(|| self.id.cmp(&other.id)).
+		then_with(|| {
+			let keys = self.clocks.keys().chain(other.clocks.keys()).collect::<BTreeSet<_>>();
+			keys.into_iter().map(|k| self.get(k).cmp(&other.get(k))).find(|&x| x != Ordering::Equal).unwrap_or(Ordering::Equal)
+		})
+	}
+}
+
+impl PartialOrd for VectorClock {
+	fn partial_cmp (&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}